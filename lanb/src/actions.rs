@@ -1,13 +1,16 @@
 use crate::{
     argparse::Args,
-    Error::{Canceled, LambError},
+    link::LinkKind,
+    Error::{Canceled, CannotOverwriteDirectory, LambError, NoFileName},
     Result,
 };
 use colored::Colorize;
 use filey::{FileTypes, Filey};
 use std::{
     fmt::Display,
+    fs::rename,
     io::{stdin, stdout, Write},
+    path::{Component, Path, PathBuf},
 };
 
 fn confirm<S: Display>(message: S) -> Result<bool> {
@@ -27,16 +30,14 @@ fn confirm<S: Display>(message: S) -> Result<bool> {
     }
 }
 
-fn check(link: &Filey, args: &Args) -> Result<()> {
+pub fn check(link: &Filey, args: &Args) -> Result<()> {
     if link.exists() {
-        if args.backup() {
-            let mut link = link.clone();
-            let backup = link
-                .move_to(format!("{}{}", link, args.suffix()))
+        if let Some(backup) = args.backup()?.path(link.path(), &args.suffix()) {
+            rename(link.path(), &backup)
                 .map_err(|e| e.into())
                 .map_err(LambError)?;
             if !args.quiet() {
-                eprintln!("{} backup '{}'", "Created".green().bold(), backup);
+                eprintln!("{} backup '{}'", "Created".green().bold(), backup.display());
             }
             Ok(())
         } else if !args.noninteractive() {
@@ -65,17 +66,18 @@ fn check(link: &Filey, args: &Args) -> Result<()> {
 }
 
 fn prepare(path: &String, args: &Args) -> Result<String> {
-    let length = args.file().len();
-    let tail = Filey::new(&args.file()[length - 1]);
+    let tail = Filey::new(args.destination());
     let call_closure = |path: &String, file_type: &FileTypes| -> Result<String> {
         match file_type {
+            // With --no-target-directory, the destination is the link itself.
+            FileTypes::Directory if args.no_target_directory() => Err(CannotOverwriteDirectory {
+                path: tail.to_string(),
+            }),
             FileTypes::Directory => {
-                let original = Filey::new(path);
-                let link = Filey::new(format!(
-                    "{}/{}",
-                    tail,
-                    original.file_name().expect("Failed to get file name")
-                ));
+                let file_name = Filey::new(path)
+                    .file_name()
+                    .ok_or_else(|| NoFileName { path: path.clone() })?;
+                let link = Filey::new(tail.path().join(file_name));
                 check(&link, args)?;
 
                 Ok(link.to_string())
@@ -86,104 +88,220 @@ fn prepare(path: &String, args: &Args) -> Result<String> {
             }
         }
     };
-    match &tail.file_type() {
+    // A destination may be a symbolic link to a directory, which is followed as argparse does,
+    // unless it is the link itself with --no-target-directory.
+    let file_type = if args.target_directory().is_some()
+        || (!args.no_target_directory() && tail.path().is_dir())
+    {
+        Some(FileTypes::Directory)
+    } else {
+        tail.file_type()
+    };
+    match &file_type {
         Some(file_type) => Ok(call_closure(path, file_type)?),
         None => Ok(tail.to_string()),
     }
 }
 
-pub fn symlink(args: &Args) -> Result<()> {
-    let length = args.file().len();
-    if length == 2 {
-        let original = &args.file()[0];
-        match prepare(original, args) {
-            Ok(link) => {
-                Filey::new(original)
-                    .symlink(&link)
-                    .map_err(|e| e.into())
-                    .map_err(LambError)?;
-                if !args.quiet() {
-                    eprintln!(
-                        "{} symlink '{}' -> '{}'",
-                        "Created".green().bold(),
-                        original,
-                        link
-                    )
-                }
+// The path a link points to.
+// It is the absolute path to the original, or for a symbolic link with --relative, the shortest
+// path to it from the directory of the link, so that links work from any working directory.
+pub fn link_target(kind: LinkKind, original: &str, link: &str, args: &Args) -> Result<PathBuf> {
+    let original = resolve(&absolutize(original)?);
+    if kind != LinkKind::Symbolic || !args.relative() {
+        return Ok(original);
+    }
+    let link = resolve(&absolutize(link)?);
+    let dir = link.parent().unwrap_or_else(|| Path::new("/"));
+    Ok(relative_path(dir, &original))
+}
+
+// Returns the path to `to` from the directory `from`. Both must be absolute.
+fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from: Vec<Component> = from.components().collect();
+    let to: Vec<Component> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut path: PathBuf = from[common..]
+        .iter()
+        .map(|_| Component::ParentDir)
+        .collect();
+    path.extend(&to[common..]);
+    if path.as_os_str().is_empty() {
+        path.push(".");
+    }
+    path
+}
+
+// Resolves symbolic links in the parent directory, but not the file itself.
+fn resolve(path: &Path) -> PathBuf {
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => parent
+            .canonicalize()
+            .map(|p| p.join(name))
+            .unwrap_or_else(|_| path.to_path_buf()),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn absolutize(path: &str) -> Result<PathBuf> {
+    let mut path = Filey::new(path);
+    path.expand_user()
+        .map_err(|e| e.into())
+        .map_err(LambError)?
+        .absolutize()
+        .map_err(|e| e.into())
+        .map_err(LambError)?;
+    Ok(path.path().to_path_buf())
+}
+
+// Create links of the kind given by the options, nth -> last or nth -> last/name_of_nth_file
+pub fn link(args: &Args) -> Result<()> {
+    let kind = args.link_kind();
+    for original in args.sources() {
+        let link = match prepare(original, args) {
+            Ok(link) => link,
+            Err(Canceled) => {
+                eprintln!("Canceled");
+                continue;
             }
-            Err(e) => match e {
-                Canceled => {
-                    eprintln!("Canceled");
-                    return Ok(());
-                }
-                _ => return Err(e),
-            },
+            Err(e) => return Err(e),
         };
-    } else {
-        for i in 0..(length - 1) {
-            let original = &args.file()[i];
-            let link = prepare(original, args)?;
-            Filey::new(original)
-                .symlink(&link)
-                .map_err(|e| e.into())
-                .map_err(LambError)?;
-            if !args.quiet() {
-                eprintln!(
-                    "{} symlink '{}' -> '{}'",
-                    "Created".green().bold(),
-                    original,
-                    link
-                )
-            }
+        let target = link_target(kind, original, &link, args)?;
+        kind.create(&target, Path::new(&link))?;
+        if !args.quiet() {
+            eprintln!(
+                "{} {} '{}' {} '{}'",
+                "Created".green().bold(),
+                kind,
+                target.display(),
+                kind.arrow(),
+                link
+            )
         }
     }
     Ok(())
 }
 
-pub fn hardlink(args: &Args) -> Result<()> {
-    let length = args.file().len();
-    if length == 2 {
-        let original = &args.file()[0];
-        match prepare(original, args) {
-            Ok(link) => {
-                Filey::new(original)
-                    .hard_link(&link)
-                    .map_err(|e| e.into())
-                    .map_err(LambError)?;
-                if !args.quiet() {
-                    eprintln!(
-                        "{} hard link '{}' => '{}'",
-                        "Created".green().bold(),
-                        original,
-                        link
-                    )
-                }
-            }
-            Err(e) => match e {
-                Canceled => {
-                    eprintln!("Canceled");
-                    return Ok(());
-                }
-                _ => return Err(e),
-            },
-        };
-    } else {
-        for i in 0..(length - 1) {
-            let original = &args.file()[i];
-            let link = prepare(original, args)?;
-            Filey::new(original)
-                .symlink(&link)
-                .map_err(|e| e.into())
-                .map_err(LambError)?;
-            if !args.quiet() {
-                eprintln!(
-                    "{} symlink '{}' -> '{}'",
-                    "Created".green().bold(),
-                    original,
-                    link
-                )
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::{
+        env,
+        fs::{canonicalize, create_dir_all, read_link, remove_dir_all, symlink_metadata, write},
+        os::unix::fs::{symlink, MetadataExt},
+        process,
+    };
+
+    #[test]
+    fn relative() {
+        let path = |from: &str, to: &str| relative_path(Path::new(from), Path::new(to));
+        assert_eq!(path("/a/b", "/a/b/t"), Path::new("t"));
+        assert_eq!(path("/a/b", "/a/t"), Path::new("../t"));
+        assert_eq!(path("/a/b", "/c/d/t"), Path::new("../../c/d/t"));
+        assert_eq!(path("/a/b", "/a/b"), Path::new("."));
+        assert_eq!(path("/", "/a/t"), Path::new("a/t"));
+    }
+
+    #[test]
+    fn resolve_parent_only() {
+        let test = env::temp_dir().join(format!("lanb-test-resolve-{}", process::id()));
+        let _ = remove_dir_all(&test);
+        create_dir_all(test.join("x/y")).unwrap();
+        create_dir_all(test.join("a")).unwrap();
+        write(test.join("a/t"), "t").unwrap();
+        let test = canonicalize(&test).unwrap();
+        symlink(test.join("x/y"), test.join("ld")).unwrap();
+        symlink(test.join("a/t"), test.join("x/y/t")).unwrap();
+        assert_eq!(resolve(&test.join("ld/l")), test.join("x/y/l"));
+        // The file itself isn't resolved.
+        assert_eq!(resolve(&test.join("ld/t")), test.join("x/y/t"));
+        // A link in a directory which is itself a symbolic link is made relative to the real one.
+        let args = Args::parse_from(["lanb", "-r", "a", "b"]);
+        let link = test.join("ld/l").display().to_string();
+        let original = test.join("a/t").display().to_string();
+        let target = link_target(LinkKind::Symbolic, &original, &link, &args).unwrap();
+        assert_eq!(target, Path::new("../../a/t"));
+        symlink(&target, &link).unwrap();
+        assert_eq!(canonicalize(&link).unwrap(), test.join("a/t"));
+        remove_dir_all(&test).unwrap();
+    }
+
+    #[test]
+    fn hard_links_into_directory() {
+        let test = env::temp_dir().join(format!("lanb-test-hard-{}", process::id()));
+        let _ = remove_dir_all(&test);
+        create_dir_all(test.join("dir")).unwrap();
+        write(test.join("a"), "a").unwrap();
+        write(test.join("b"), "b").unwrap();
+        let path = |name: &str| test.join(name).display().to_string();
+        let args = Args::parse_from(["lanb", "-q", "-H", &path("a"), &path("b"), &path("dir")]);
+        link(&args).unwrap();
+        for name in ["a", "b"] {
+            let original = symlink_metadata(test.join(name)).unwrap();
+            let linked = symlink_metadata(test.join("dir").join(name)).unwrap();
+            assert!(!linked.file_type().is_symlink());
+            assert_eq!(linked.ino(), original.ino());
+            assert_eq!(linked.nlink(), 2);
         }
+        remove_dir_all(&test).unwrap();
+    }
+
+    // A directory with files `a` and `b` and a directory `d`, and a symbolic link `ld` to `d`.
+    fn setup(name: &str) -> PathBuf {
+        let test = env::temp_dir().join(format!("lanb-test-{}-{}", name, process::id()));
+        let _ = remove_dir_all(&test);
+        create_dir_all(test.join("d")).unwrap();
+        write(test.join("a"), "a").unwrap();
+        write(test.join("b"), "b").unwrap();
+        let test = canonicalize(&test).unwrap();
+        symlink(test.join("d"), test.join("ld")).unwrap();
+        test
+    }
+
+    // Options are given as they are, and file names relative to the test directory.
+    fn parse(test: &Path, args: &[&str]) -> Args {
+        let args = args.iter().map(|i| match *i {
+            i if i.starts_with('-') => i.to_string(),
+            i => test.join(i).display().to_string(),
+        });
+        Args::parse_from(
+            ["lanb".to_string(), "-q".to_string()]
+                .into_iter()
+                .chain(args),
+        )
+    }
+
+    #[test]
+    fn target_directory() {
+        let test = setup("target-directory");
+        link(&parse(&test, &["-t", "d", "a", "b"])).unwrap();
+        assert_eq!(read_link(test.join("d/a")).unwrap(), test.join("a"));
+        assert_eq!(read_link(test.join("d/b")).unwrap(), test.join("b"));
+        // A link to a directory is followed.
+        link(&parse(&test, &["-n", "-t", "ld", "a"])).unwrap();
+        assert_eq!(read_link(test.join("d/a")).unwrap(), test.join("a"));
+        assert!(symlink_metadata(test.join("ld")).unwrap().is_symlink());
+        // A source without a file name is an error, not a panic.
+        let args = Args::parse_from(["lanb", "-q", "-t", test.join("d").to_str().unwrap(), "/"]);
+        assert!(matches!(link(&args), Err(NoFileName { .. })));
+        remove_dir_all(&test).unwrap();
+    }
+
+    #[test]
+    fn no_target_directory() {
+        let test = setup("no-target-directory");
+        assert!(matches!(
+            link(&parse(&test, &["-n", "-T", "a", "d"])),
+            Err(CannotOverwriteDirectory { .. })
+        ));
+        assert!(test.join("d").is_dir());
+        assert!(!test.join("d/a").exists());
+        // The link to a directory is replaced itself.
+        link(&parse(&test, &["-n", "-T", "a", "ld"])).unwrap();
+        assert_eq!(read_link(test.join("ld")).unwrap(), test.join("a"));
+        assert!(test.join("d").is_dir());
+        assert!(!test.join("d/a").exists());
+        remove_dir_all(&test).unwrap();
     }
-    Ok(())
 }
@@ -1,10 +1,13 @@
 use crate::{
-    actions::{hardlink, symlink},
-    Error::{LambError, NotADirectory},
+    actions::link,
+    backup::{self, Backup},
+    link::LinkKind,
+    stow::{restow, stow, unstow},
+    Error::{InvalidBackup, LambError, NotADirectory},
     Result,
 };
-use clap::{error::ErrorKind, Command, Parser};
-use filey::{FileTypes, Filey};
+use clap::{error::ErrorKind, ArgGroup, Command, Parser};
+use filey::Filey;
 
 /// lanb
 ///
@@ -12,39 +15,115 @@ use filey::{FileTypes, Filey};
 ///
 /// If two files are specified, create symbolic link 1st -> 2nd.
 /// If three files or more are specified, create symbolic nth -> last/name_of_nth_file.
+/// With -t DIR, create symbolic links nth -> DIR/name_of_nth_file.
 /// If you do not use the option --hardlink, create symbolic links by default.
+/// With --stow PKG_DIR --target DIR, link the tree of PKG_DIR into DIR like GNU stow.
 #[derive(Debug, Parser)]
+#[clap(group(ArgGroup::new("stowing").args(["stow", "unstow", "restow"]).requires("target").conflicts_with_all(["file", "hardlink", "target_directory", "no_target_directory"])))]
 #[clap(name = env!("CARGO_PKG_NAME"), version = env!("CARGO_PKG_VERSION"), author = env!("CARGO_PKG_AUTHORS"), about = env!("CARGO_PKG_DESCRIPTION"), arg_required_else_help = true, verbatim_doc_comment)]
 pub struct Args {
     file: Vec<String>,
     /// Make backup of each existing destination file.
-    #[clap(short, long)]
-    backup: bool,
-    /// Override the usual backup suffix.
-    #[clap(short, long = "suffix", default_value = "~")]
-    suffix: String,
+    /// CONTROL is none(off), numbered(t), existing(nil) or simple(never).
+    /// Without CONTROL, $VERSION_CONTROL is used, or existing if it isn't set.
+    #[clap(short, long, value_name = "CONTROL", num_args = 0..=1, require_equals = true, default_missing_value = "", verbatim_doc_comment)]
+    backup: Option<String>,
+    /// Override the usual backup suffix(~ or $SIMPLE_BACKUP_SUFFIX). Implies --backup.
+    #[clap(short, long = "suffix")]
+    suffix: Option<String>,
     /// Do not prompt whether to remove destinations.
     #[clap(short, long)]
     noninteractive: bool,
     /// Make hard links instead of symbolic links.
     #[clap(short = 'H', long)]
     hardlink: bool,
+    /// Make symbolic links point to absolute paths of their targets. This is the default.
+    #[clap(long, conflicts_with = "relative")]
+    absolute: bool,
+    /// Make symbolic links point to their targets relative to the links' directories.
+    #[clap(short, long, conflicts_with = "hardlink")]
+    relative: bool,
+    /// Create links in DIR to all the given files.
+    #[clap(
+        short,
+        long,
+        value_name = "DIR",
+        conflicts_with = "no_target_directory"
+    )]
+    target_directory: Option<String>,
+    /// Treat the last file as the name of the link even if it is a directory.
+    #[clap(short = 'T', long)]
+    no_target_directory: bool,
+    /// Link the tree of PKG_DIR into the directory given by --target, folding directories
+    /// which don't exist there into single links. Nothing is changed if files are in the way.
+    #[clap(long, value_name = "PKG_DIR", verbatim_doc_comment)]
+    stow: Option<String>,
+    /// Remove the links which --stow made from the directory given by --target.
+    #[clap(long, value_name = "PKG_DIR")]
+    unstow: Option<String>,
+    /// Unstow and stow PKG_DIR again.
+    #[clap(long, value_name = "PKG_DIR")]
+    restow: Option<String>,
+    /// The directory to stow packages into.
+    #[clap(long, value_name = "DIR", requires = "stowing")]
+    target: Option<String>,
     /// Do not print name of each linked file.
     #[clap(short, long)]
     quiet: bool,
 }
 
 impl Args {
-    pub fn file(&self) -> &Vec<String> {
-        &self.file
+    /// Returns the files to create links to.
+    pub fn sources(&self) -> &[String] {
+        match &self.target_directory {
+            Some(_) => &self.file,
+            None => &self.file[..self.file.len().saturating_sub(1)],
+        }
+    }
+
+    /// Returns the directory to create links in, or the name of the link.
+    pub fn destination(&self) -> &String {
+        self.target_directory
+            .as_ref()
+            .unwrap_or_else(|| &self.file[self.file.len() - 1])
     }
 
-    pub fn backup(&self) -> bool {
-        self.backup
+    pub fn target_directory(&self) -> Option<&String> {
+        self.target_directory.as_ref()
     }
 
-    pub fn suffix(&self) -> &String {
-        &self.suffix
+    pub fn no_target_directory(&self) -> bool {
+        self.no_target_directory
+    }
+
+    /// Returns how existing destinations are backed up.
+    pub fn backup(&self) -> Result<Backup> {
+        match (&self.backup, &self.suffix) {
+            (Some(control), _) if !control.is_empty() => {
+                control.parse().map_err(|_| InvalidBackup {
+                    value: control.clone(),
+                    name: "--backup".to_string(),
+                })
+            }
+            (None, None) => Ok(Backup::None),
+            _ => Backup::from_env(),
+        }
+    }
+
+    pub fn suffix(&self) -> String {
+        backup::suffix(self.suffix.as_ref())
+    }
+
+    pub fn link_kind(&self) -> LinkKind {
+        if self.hardlink {
+            LinkKind::Hard
+        } else {
+            LinkKind::Symbolic
+        }
+    }
+
+    pub fn relative(&self) -> bool {
+        self.relative
     }
 
     pub fn noninteractive(&self) -> bool {
@@ -57,23 +136,72 @@ impl Args {
 }
 
 pub fn argparse() -> Result<()> {
-    let args = Args::parse();
+    run(Args::parse())
+}
+
+fn run(args: Args) -> Result<()> {
+    // Report an invalid backup control before linking anything.
+    args.backup()?;
+    if let Some(target) = &args.target {
+        return match (&args.stow, &args.unstow, &args.restow) {
+            (Some(dir), _, _) => stow(dir, target, &args),
+            (_, Some(dir), _) => unstow(dir, target, &args),
+            (_, _, Some(dir)) => restow(dir, target, &args),
+            _ => Ok(()),
+        };
+    }
     let mut cmd = Command::new("lamb");
-    let length = args.file.len();
-    let tail = Filey::new(&args.file[length - 1]);
-    if length < 2 {
+    let length = args.sources().len();
+    if length == 0 {
         let e = cmd.error(ErrorKind::DisplayHelp, "Incorrect Arguments");
+        return Err(e).map_err(|e| e.into()).map_err(LambError);
+    }
+    let tail = Filey::new(args.destination());
+    if args.no_target_directory && length > 1 {
+        let e = cmd.error(
+            ErrorKind::TooManyValues,
+            format!("extra operand '{}'", args.file[2]),
+        );
         Err(e).map_err(|e| e.into()).map_err(LambError)
-    } else if length > 2 && tail.file_type().unwrap_or(FileTypes::File) != FileTypes::Directory {
+    } else if (length > 1 || args.target_directory.is_some()) && !tail.path().is_dir() {
         Err(NotADirectory {
             path: tail.to_string(),
         })
     } else {
-        if args.hardlink {
-            hardlink(&args)?;
-        } else {
-            symlink(&args)?;
-        }
-        Ok(())
+        link(&args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        env,
+        fs::{create_dir_all, remove_dir_all, symlink_metadata, write},
+        process,
+    };
+
+    #[test]
+    fn operands() {
+        let test = env::temp_dir().join(format!("lanb-test-operands-{}", process::id()));
+        let _ = remove_dir_all(&test);
+        create_dir_all(test.join("d")).unwrap();
+        write(test.join("a"), "a").unwrap();
+        write(test.join("b"), "b").unwrap();
+        let path = |name: &str| test.join(name).display().to_string();
+        let parse = |args: &[&str]| Args::parse_from(["lanb", "-q"].iter().chain(args));
+        // -T takes a single source.
+        let args = parse(&["-T", &path("a"), &path("b"), &path("c")]);
+        assert!(matches!(run(args), Err(LambError(_))));
+        assert!(symlink_metadata(path("c")).is_err());
+        // Several sources and -t need a directory.
+        let args = parse(&[&path("a"), &path("b"), &path("c")]);
+        assert!(matches!(run(args), Err(NotADirectory { .. })));
+        let args = parse(&["-t", &path("a"), &path("b")]);
+        assert!(matches!(run(args), Err(NotADirectory { .. })));
+        assert!(symlink_metadata(path("a")).unwrap().is_file());
+        run(parse(&["-t", &path("d"), &path("a"), &path("b")])).unwrap();
+        assert!(symlink_metadata(path("d/a")).unwrap().is_symlink());
+        remove_dir_all(&test).unwrap();
     }
 }
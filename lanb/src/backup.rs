@@ -0,0 +1,199 @@
+use crate::{Error::InvalidBackup, Result};
+use std::{
+    env,
+    fs::read_dir,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// How existing destinations are backed up, like the version control of GNU ln.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backup {
+    /// Never make backups.
+    None,
+    /// Make numbered backups like foo.~1~, foo.~2~.
+    Numbered,
+    /// Make numbered backups of files which already have them, and simple backups of the others.
+    Existing,
+    /// Make simple backups like foo~.
+    Simple,
+}
+
+impl Backup {
+    /// Reads the backup control from $VERSION_CONTROL, or returns the default.
+    pub fn from_env() -> Result<Self> {
+        match env::var("VERSION_CONTROL") {
+            Ok(value) if !value.is_empty() => value.parse().map_err(|_| InvalidBackup {
+                value,
+                name: "$VERSION_CONTROL".to_string(),
+            }),
+            _ => Ok(Backup::Existing),
+        }
+    }
+
+    /// Returns the path to back up `path` to, or None if it isn't backed up.
+    pub fn path(&self, path: &Path, suffix: &str) -> Option<PathBuf> {
+        let simple = || {
+            let mut backup = path.as_os_str().to_owned();
+            backup.push(suffix);
+            PathBuf::from(backup)
+        };
+        match self {
+            Backup::None => None,
+            Backup::Simple => Some(simple()),
+            Backup::Numbered => Some(numbered(path, last_number(path).unwrap_or(0) + 1)),
+            Backup::Existing => match last_number(path) {
+                Some(n) => Some(numbered(path, n + 1)),
+                None => Some(simple()),
+            },
+        }
+    }
+}
+
+impl FromStr for Backup {
+    type Err = ();
+
+    // The names and their aliases which GNU ln accepts.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "none" | "off" => Ok(Backup::None),
+            "numbered" | "t" => Ok(Backup::Numbered),
+            "existing" | "nil" => Ok(Backup::Existing),
+            "simple" | "never" => Ok(Backup::Simple),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Returns the suffix of simple backups: the given one, $SIMPLE_BACKUP_SUFFIX, or "~".
+pub fn suffix(given: Option<&String>) -> String {
+    match given {
+        Some(suffix) => suffix.clone(),
+        None => env::var("SIMPLE_BACKUP_SUFFIX")
+            .ok()
+            .filter(|s| !s.is_empty() && !s.contains('/'))
+            .unwrap_or_else(|| "~".to_string()),
+    }
+}
+
+fn numbered(path: &Path, n: u64) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".~{}~", n));
+    PathBuf::from(backup)
+}
+
+// Returns the largest number of the existing numbered backups of a path.
+fn last_number(path: &Path) -> Option<u64> {
+    let name = path.file_name()?.to_str()?;
+    let dir = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    read_dir(dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            e.file_name()
+                .to_str()?
+                .strip_prefix(name)?
+                .strip_prefix(".~")?
+                .strip_suffix('~')?
+                .parse()
+                .ok()
+        })
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        fs::{create_dir_all, remove_dir_all, write},
+        process,
+    };
+
+    fn setup(name: &str, files: &[&str]) -> PathBuf {
+        let test = env::temp_dir().join(format!("lanb-test-backup-{}-{}", name, process::id()));
+        let _ = remove_dir_all(&test);
+        create_dir_all(&test).unwrap();
+        for i in files {
+            write(test.join(i), "").unwrap();
+        }
+        test
+    }
+
+    #[test]
+    fn path() {
+        let test = setup("path", &["l", "l.~1~", "l.~2~"]);
+        let l = test.join("l");
+        assert_eq!(Backup::None.path(&l, "~"), None);
+        assert_eq!(Backup::Simple.path(&l, ".bak"), Some(test.join("l.bak")));
+        assert_eq!(Backup::Numbered.path(&l, "~"), Some(test.join("l.~3~")));
+        assert_eq!(Backup::Existing.path(&l, "~"), Some(test.join("l.~3~")));
+        remove_dir_all(&test).unwrap();
+    }
+
+    #[test]
+    fn last_number_skips_others() {
+        let test = setup("last", &["l", "l.~9~", "l.~x~", "l.~10", "ll.~20~"]);
+        let l = test.join("l");
+        assert_eq!(last_number(&l), Some(9));
+        assert_eq!(Backup::Numbered.path(&l, "~"), Some(test.join("l.~10~")));
+        assert_eq!(last_number(&test.join("ll")), Some(20));
+        assert_eq!(last_number(&test.join("m")), None);
+        remove_dir_all(&test).unwrap();
+    }
+
+    #[test]
+    fn existing_falls_back_to_simple() {
+        let test = setup("existing", &["l", "m", "m.~1~"]);
+        assert_eq!(
+            Backup::Existing.path(&test.join("l"), "~"),
+            Some(test.join("l~"))
+        );
+        assert_eq!(
+            Backup::Existing.path(&test.join("m"), "~"),
+            Some(test.join("m.~2~"))
+        );
+        assert_eq!(
+            Backup::Numbered.path(&test.join("l"), "~"),
+            Some(test.join("l.~1~"))
+        );
+        remove_dir_all(&test).unwrap();
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!("off".parse(), Ok(Backup::None));
+        assert_eq!("t".parse(), Ok(Backup::Numbered));
+        assert_eq!("nil".parse(), Ok(Backup::Existing));
+        assert_eq!("never".parse(), Ok(Backup::Simple));
+        assert_eq!("always".parse::<Backup>(), Err(()));
+    }
+
+    // In one test, since the environment is shared by all of them.
+    #[test]
+    fn environment() {
+        env::remove_var("VERSION_CONTROL");
+        assert_eq!(Backup::from_env().unwrap(), Backup::Existing);
+        env::set_var("VERSION_CONTROL", "numbered");
+        assert_eq!(Backup::from_env().unwrap(), Backup::Numbered);
+        env::set_var("VERSION_CONTROL", "sometimes");
+        assert!(matches!(
+            Backup::from_env(),
+            Err(InvalidBackup { value, .. }) if value == "sometimes"
+        ));
+        env::remove_var("VERSION_CONTROL");
+
+        env::remove_var("SIMPLE_BACKUP_SUFFIX");
+        assert_eq!(suffix(None), "~");
+        assert_eq!(suffix(Some(&".old".to_string())), ".old");
+        env::set_var("SIMPLE_BACKUP_SUFFIX", ".bak");
+        assert_eq!(suffix(None), ".bak");
+        assert_eq!(suffix(Some(&".old".to_string())), ".old");
+        // A suffix which would move the backup into another directory is ignored.
+        env::set_var("SIMPLE_BACKUP_SUFFIX", "/bak");
+        assert_eq!(suffix(None), "~");
+        env::remove_var("SIMPLE_BACKUP_SUFFIX");
+    }
+}
@@ -0,0 +1,43 @@
+use crate::{Error::LambError, Result};
+use std::{
+    fmt::{self, Display},
+    fs::hard_link,
+    os::unix::fs::symlink,
+    path::Path,
+};
+
+/// Kinds of links lanb can create.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkKind {
+    Symbolic,
+    Hard,
+}
+
+impl LinkKind {
+    /// Creates a link of this kind at `link` which points to `target`.
+    pub fn create(&self, target: &Path, link: &Path) -> Result<()> {
+        match self {
+            LinkKind::Symbolic => symlink(target, link),
+            LinkKind::Hard => hard_link(target, link),
+        }
+        .map_err(|e| e.into())
+        .map_err(LambError)
+    }
+
+    /// Returns the arrow printed between a target and its link.
+    pub fn arrow(&self) -> &'static str {
+        match self {
+            LinkKind::Symbolic => "->",
+            LinkKind::Hard => "=>",
+        }
+    }
+}
+
+impl Display for LinkKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkKind::Symbolic => write!(f, "symlink"),
+            LinkKind::Hard => write!(f, "hard link"),
+        }
+    }
+}
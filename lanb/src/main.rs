@@ -1,5 +1,8 @@
 mod actions;
 mod argparse;
+mod backup;
+mod link;
+mod stow;
 
 use crate::argparse::argparse;
 use std::process::exit;
@@ -19,6 +22,28 @@ pub enum Error {
     NotADirectory {
         path: String,
     },
+    #[error(
+        "invalid argument '{}' for '{}'(valid arguments are none, numbered, existing and simple)",
+        value,
+        name
+    )]
+    InvalidBackup {
+        value: String,
+        name: String,
+    },
+    #[error("'{}' has no file name to name a link after", path)]
+    NoFileName {
+        path: String,
+    },
+    #[error("cannot overwrite directory '{}'", path)]
+    CannotOverwriteDirectory {
+        path: String,
+    },
+    #[error("'{}' wasn't stowed, conflicting paths: {}", path, count)]
+    Conflicts {
+        path: String,
+        count: usize,
+    },
     #[error("Canceled")]
     Canceled,
 }
@@ -0,0 +1,405 @@
+use crate::{
+    actions::{check, link_target},
+    argparse::Args,
+    backup::Backup,
+    link::LinkKind,
+    Error::{Conflicts, LambError, NotADirectory},
+    Result,
+};
+use colored::Colorize;
+use filey::Filey;
+use std::{
+    fs::{canonicalize, create_dir, read_dir, remove_file, symlink_metadata},
+    path::{Path, PathBuf},
+};
+
+// Files which are never linked from a package.
+const IGNORED: [&str; 1] = [".git"];
+
+// A change to the target directory.
+#[derive(Debug)]
+enum Step {
+    // Create a symbolic link to a file or a directory in the package.
+    Link { source: PathBuf, link: PathBuf },
+    // Back up an existing file with --backup, then link it.
+    Replace { source: PathBuf, link: PathBuf },
+    // Replace a link to a directory of another package with a real directory
+    // of links to its entries, so that entries of this package can be added to it.
+    Unfold { link: PathBuf, dir: PathBuf },
+    // Remove a symbolic link to the package.
+    Unlink { link: PathBuf },
+}
+
+// What a path in the target directory is.
+enum Existing {
+    Missing,
+    // A symbolic link and where it finally points to.
+    Link(PathBuf),
+    Directory,
+    Other,
+}
+
+// A package directory and the target directory it is stowed into.
+struct Package<'a> {
+    dir: PathBuf,
+    // Where packages live. Links into it are ones made by stowing, so they can be unfolded.
+    stow_dir: PathBuf,
+    target: PathBuf,
+    args: &'a Args,
+}
+
+impl<'a> Package<'a> {
+    fn new(dir: &str, target: &str, args: &'a Args) -> Result<Self> {
+        let canonical = |path: &str| {
+            if !Path::new(path).is_dir() {
+                return Err(NotADirectory {
+                    path: path.to_string(),
+                });
+            }
+            canonicalize(path).map_err(|e| e.into()).map_err(LambError)
+        };
+        let dir = canonical(dir)?;
+        let stow_dir = dir.parent().unwrap_or(&dir).to_path_buf();
+        Ok(Package {
+            dir,
+            stow_dir,
+            target: canonical(target)?,
+            args,
+        })
+    }
+
+    // Plan links from the target to the package, folding directories which don't exist in the
+    // target into single links. Paths which are in the way are returned as conflicts.
+    fn plan_stow(&self) -> Result<(Vec<Step>, Vec<PathBuf>)> {
+        let mut steps = vec![];
+        let mut conflicts = vec![];
+        self.stow_dir(&self.dir, &self.target, None, &mut steps, &mut conflicts)?;
+        Ok((steps, conflicts))
+    }
+
+    // `unfolded` is the directory a target directory will be unfolded from, whose entries are
+    // linked in it then.
+    fn stow_dir(
+        &self,
+        source: &Path,
+        target: &Path,
+        unfolded: Option<&Path>,
+        steps: &mut Vec<Step>,
+        conflicts: &mut Vec<PathBuf>,
+    ) -> Result<()> {
+        for name in entries(source)? {
+            let source = source.join(&name);
+            let link = target.join(&name);
+            let resolved = canonicalize(&source).unwrap_or_else(|_| source.clone());
+            let existing = match unfolded {
+                Some(dir) => match canonicalize(dir.join(&name)) {
+                    Ok(p) => Existing::Link(p),
+                    Err(_) => Existing::Missing,
+                },
+                None => existing(&link),
+            };
+            match existing {
+                Existing::Missing => steps.push(Step::Link { source, link }),
+                Existing::Link(p) if p == resolved => (),
+                Existing::Link(p) if p.is_dir() && source.is_dir() && self.is_stowed(&p) => {
+                    steps.push(Step::Unfold {
+                        link: link.clone(),
+                        dir: p.clone(),
+                    });
+                    self.stow_dir(&source, &link, Some(&p), steps, conflicts)?;
+                }
+                Existing::Directory if source.is_dir() => {
+                    self.stow_dir(&source, &link, None, steps, conflicts)?
+                }
+                _ if self.args.backup()? != Backup::None => {
+                    steps.push(Step::Replace { source, link })
+                }
+                _ => conflicts.push(link),
+            }
+        }
+        Ok(())
+    }
+
+    // Plan removing links from the target to the package. Nothing else is touched.
+    fn plan_unstow(&self) -> Result<Vec<Step>> {
+        let mut steps = vec![];
+        self.unstow_dir(&self.dir, &self.target, &mut steps)?;
+        Ok(steps)
+    }
+
+    fn unstow_dir(&self, source: &Path, target: &Path, steps: &mut Vec<Step>) -> Result<()> {
+        for name in entries(source)? {
+            let source = source.join(&name);
+            let link = target.join(&name);
+            let resolved = canonicalize(&source).unwrap_or_else(|_| source.clone());
+            match existing(&link) {
+                Existing::Link(p) if p == resolved => steps.push(Step::Unlink { link }),
+                Existing::Directory if source.is_dir() => self.unstow_dir(&source, &link, steps)?,
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    // Links into another package in the stow directory were made by stowing it.
+    fn is_stowed(&self, path: &Path) -> bool {
+        path.starts_with(&self.stow_dir) && !path.starts_with(&self.dir)
+    }
+
+    fn run(&self, steps: Vec<Step>) -> Result<()> {
+        let kind = LinkKind::Symbolic;
+        let quiet = self.args.quiet();
+        for step in steps {
+            match step {
+                Step::Link { source, link } => self.link(&source, &link)?,
+                Step::Replace { source, link } => {
+                    check(&Filey::new(&link), self.args)?;
+                    self.link(&source, &link)?;
+                }
+                Step::Unfold { link, dir } => {
+                    remove_file(&link)
+                        .and_then(|_| create_dir(&link))
+                        .map_err(|e| e.into())
+                        .map_err(LambError)?;
+                    for name in entries(&dir)? {
+                        self.link(&dir.join(&name), &link.join(&name))?;
+                    }
+                    if !quiet {
+                        eprintln!("{} '{}'", "Unfolded".green().bold(), link.display());
+                    }
+                }
+                Step::Unlink { link } => {
+                    remove_file(&link)
+                        .map_err(|e| e.into())
+                        .map_err(LambError)?;
+                    if !quiet {
+                        eprintln!("{} {} '{}'", "Removed".green().bold(), kind, link.display());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn link(&self, source: &Path, link: &Path) -> Result<()> {
+        let kind = LinkKind::Symbolic;
+        let target = link_target(
+            kind,
+            &source.display().to_string(),
+            &link.display().to_string(),
+            self.args,
+        )?;
+        kind.create(&target, link)?;
+        if !self.args.quiet() {
+            eprintln!(
+                "{} {} '{}' {} '{}'",
+                "Created".green().bold(),
+                kind,
+                target.display(),
+                kind.arrow(),
+                link.display()
+            )
+        }
+        Ok(())
+    }
+}
+
+// Returns the names in a directory which are linked, in order.
+fn entries(dir: &Path) -> Result<Vec<String>> {
+    let mut names: Vec<String> = read_dir(dir)
+        .map_err(|e| e.into())
+        .map_err(LambError)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .filter(|name| !IGNORED.contains(&name.as_str()))
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+fn existing(path: &Path) -> Existing {
+    match symlink_metadata(path) {
+        Err(_) => Existing::Missing,
+        Ok(m) if m.file_type().is_symlink() => match canonicalize(path) {
+            Ok(p) => Existing::Link(p),
+            // A dangling link is in the way like a file.
+            Err(_) => Existing::Other,
+        },
+        Ok(m) if m.is_dir() => Existing::Directory,
+        Ok(_) => Existing::Other,
+    }
+}
+
+// Nothing is changed if anything is in the way.
+fn report_conflicts(package: &Package, conflicts: &[PathBuf]) -> Result<()> {
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+    for i in conflicts {
+        eprintln!(
+            "{} '{}' already exists and isn't a link into '{}'",
+            "Conflict".red().bold(),
+            i.display(),
+            package.dir.display()
+        );
+    }
+    Err(Conflicts {
+        path: package.dir.display().to_string(),
+        count: conflicts.len(),
+    })
+}
+
+// Link the tree of a package directory into the target directory like GNU stow
+pub fn stow(dir: &str, target: &str, args: &Args) -> Result<()> {
+    let package = Package::new(dir, target, args)?;
+    let (steps, conflicts) = package.plan_stow()?;
+    report_conflicts(&package, &conflicts)?;
+    package.run(steps)
+}
+
+// Remove the links which stowing a package directory made in the target directory
+pub fn unstow(dir: &str, target: &str, args: &Args) -> Result<()> {
+    let package = Package::new(dir, target, args)?;
+    let steps = package.plan_unstow()?;
+    package.run(steps)
+}
+
+// Unstow and stow a package directory again, e.g. after files were added to or removed from it
+// Conflicts are checked before the links are removed.
+pub fn restow(dir: &str, target: &str, args: &Args) -> Result<()> {
+    let package = Package::new(dir, target, args)?;
+    let (_, conflicts) = package.plan_stow()?;
+    report_conflicts(&package, &conflicts)?;
+    package.run(package.plan_unstow()?)?;
+    let (steps, _) = package.plan_stow()?;
+    package.run(steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::{
+        env,
+        fs::{create_dir_all, read_link, read_to_string, remove_dir_all, write},
+        process,
+    };
+
+    // A stow directory with packages in it and an empty target directory.
+    fn setup(name: &str) -> (PathBuf, PathBuf) {
+        let test = env::temp_dir().join(format!("lanb-test-{}-{}", name, process::id()));
+        let _ = remove_dir_all(&test);
+        create_dir_all(test.join("stow")).unwrap();
+        create_dir_all(test.join("target")).unwrap();
+        let test = canonicalize(&test).unwrap();
+        (test.join("stow"), test.join("target"))
+    }
+
+    fn args(target: &Path) -> Args {
+        Args::parse_from([
+            "lanb",
+            "-q",
+            "--stow",
+            "_",
+            "--target",
+            target.to_str().unwrap(),
+        ])
+    }
+
+    fn package(stow_dir: &Path, name: &str, files: &[&str]) -> String {
+        let dir = stow_dir.join(name);
+        for i in files {
+            create_dir_all(dir.join(i).parent().unwrap()).unwrap();
+            write(dir.join(i), name).unwrap();
+        }
+        dir.display().to_string()
+    }
+
+    fn is_symlink(path: &Path) -> bool {
+        symlink_metadata(path)
+            .map(|m| m.file_type().is_symlink())
+            .unwrap_or(false)
+    }
+
+    #[test]
+    fn fold_missing_directory() {
+        let (stow_dir, target) = setup("fold");
+        let args = args(&target);
+        let pkg = package(&stow_dir, "vim", &[".vim/vimrc", ".vim/colors/dark.vim"]);
+        let package = Package::new(&pkg, target.to_str().unwrap(), &args).unwrap();
+        let (steps, conflicts) = package.plan_stow().unwrap();
+        assert!(conflicts.is_empty());
+        assert_eq!(steps.len(), 1);
+        assert!(matches!(&steps[0], Step::Link { link, .. } if link == &target.join(".vim")));
+        package.run(steps).unwrap();
+        assert!(is_symlink(&target.join(".vim")));
+        assert_eq!(read_to_string(target.join(".vim/vimrc")).unwrap(), "vim");
+        remove_dir_all(stow_dir.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn unfold_shared_directory() {
+        let (stow_dir, target) = setup("unfold");
+        let args = args(&target);
+        let vim = package(&stow_dir, "vim", &[".config/vim/vimrc"]);
+        let git = package(&stow_dir, "git", &[".config/git/config"]);
+        stow(&vim, target.to_str().unwrap(), &args).unwrap();
+        assert!(is_symlink(&target.join(".config")));
+        let package = Package::new(&git, target.to_str().unwrap(), &args).unwrap();
+        let (steps, conflicts) = package.plan_stow().unwrap();
+        assert!(conflicts.is_empty());
+        assert!(matches!(&steps[0], Step::Unfold { link, .. } if link == &target.join(".config")));
+        package.run(steps).unwrap();
+        assert!(!is_symlink(&target.join(".config")));
+        assert!(is_symlink(&target.join(".config/vim")));
+        assert!(is_symlink(&target.join(".config/git")));
+        assert_eq!(
+            read_to_string(target.join(".config/vim/vimrc")).unwrap(),
+            "vim"
+        );
+        assert_eq!(
+            read_to_string(target.join(".config/git/config")).unwrap(),
+            "git"
+        );
+        remove_dir_all(stow_dir.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn conflict_changes_nothing() {
+        let (stow_dir, target) = setup("conflict");
+        let args = args(&target);
+        let pkg = package(&stow_dir, "bash", &[".bashrc", ".profile"]);
+        write(target.join(".bashrc"), "mine").unwrap();
+        let package = Package::new(&pkg, target.to_str().unwrap(), &args).unwrap();
+        let (_, conflicts) = package.plan_stow().unwrap();
+        assert_eq!(conflicts, vec![target.join(".bashrc")]);
+        assert!(matches!(
+            stow(&pkg, target.to_str().unwrap(), &args),
+            Err(Conflicts { count: 1, .. })
+        ));
+        assert_eq!(read_to_string(target.join(".bashrc")).unwrap(), "mine");
+        assert!(!target.join(".profile").exists());
+        remove_dir_all(stow_dir.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn unstow_keeps_other_packages() {
+        let (stow_dir, target) = setup("unstow");
+        let args = args(&target);
+        let vim = package(&stow_dir, "vim", &[".config/vim/vimrc"]);
+        let git = package(&stow_dir, "git", &[".config/git/config"]);
+        stow(&vim, target.to_str().unwrap(), &args).unwrap();
+        stow(&git, target.to_str().unwrap(), &args).unwrap();
+        let package = Package::new(&git, target.to_str().unwrap(), &args).unwrap();
+        let steps = package.plan_unstow().unwrap();
+        assert_eq!(steps.len(), 1);
+        assert!(matches!(&steps[0], Step::Unlink { link } if link == &target.join(".config/git")));
+        package.run(steps).unwrap();
+        assert!(!target.join(".config/git").exists());
+        assert_eq!(
+            read_link(target.join(".config/vim")).unwrap(),
+            stow_dir.join("vim/.config/vim")
+        );
+        remove_dir_all(stow_dir.parent().unwrap()).unwrap();
+    }
+}
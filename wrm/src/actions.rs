@@ -1,98 +1,329 @@
-use crate::{
-    file_list::{FileInfo, FileList},
-    Error::{NotFoundError, WrmError},
-    Result,
-};
+use crate::{interrupt::interrupted, report::Report, Options};
+use chrono::{DateTime, Local};
 use colored::Colorize;
-use filey::{create_dir, remove, Error::GetFileNameError, FileTypes, Filey};
+use filey::{Error::GetFileNameError, FileTypes, Filey};
+use flate2::read::GzDecoder;
 use std::{
+    collections::{BTreeMap, BTreeSet},
+    ffi::CString,
     fmt::Display,
-    io::{stdin, stdout, Write},
-    path::Path,
+    fs::{read_dir, read_link, File},
+    io::{stdin, stdout, ErrorKind, Read, Write},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+use tar::Archive;
+use walkdir::WalkDir;
+use wrm::{
+    audit_log::{AuditLog, Operation, Outcome, Record},
+    file_list::{FileInfo, FileList, Version},
+    finder::{Finder, Found},
+    git::{self, Changes},
+    hooks::{Action, Event, Hooks},
+    stats::{available, mount_point, Stats, Usage},
+    utils::{current_uid, human_size, size, user_name, users},
+    Error::{
+        AlreadyExists, DirectoryNotEmpty, IsADirectory, NotFoundError, UncommittedChanges, WrmError,
+    },
+    Result, Trash, WrmPath,
 };
 
 // Prompt before every actions.
 fn ask<D: Display>(message: D) -> Result<bool> {
-    let mut s = String::new();
+    let answer = read_answer(message)?;
+    Ok(answer.as_str() == "y" || answer.as_str() == "yes")
+}
+
+// A stronger prompt which accepts only "yes".
+fn ask_yes<D: Display>(message: D) -> Result<bool> {
+    Ok(read_answer(message)? == "yes")
+}
+
+fn read_answer<D: Display>(message: D) -> Result<String> {
     print!("{}", message);
     stdout().flush().map_err(|e| e.into()).map_err(WrmError)?;
-    stdin()
-        .read_line(&mut s)
-        .map_err(|e| e.into())
-        .map_err(WrmError)?;
-    let answer = s.trim().to_lowercase();
-    Ok(answer.as_str() == "y" || answer.as_str() == "yes")
+    // Read byte by byte rather than with read_line, which retries when Ctrl-C interrupts it.
+    // An interrupted prompt is answered with nothing, i.e. canceled.
+    let mut answer = vec![];
+    let mut stdin = stdin().lock();
+    let mut byte = [0];
+    loop {
+        match stdin.read(&mut byte) {
+            Ok(0) => break,
+            Ok(_) if byte[0] == b'\n' => break,
+            Ok(_) => answer.push(byte[0]),
+            Err(e) if e.kind() == ErrorKind::Interrupted => {
+                if interrupted() {
+                    println!();
+                    return Ok(String::new());
+                }
+            }
+            Err(e) => return Err(WrmError(e.into())),
+        }
+    }
+    Ok(String::from_utf8_lossy(&answer).trim().to_lowercase())
+}
+
+// Stop a batch when interrupted with Ctrl-C, recording the rest as not processed.
+fn stopped<S: ToString>(report: &mut Report, rest: &[S]) -> bool {
+    if interrupted() {
+        report.interrupt(rest);
+    }
+    interrupted()
 }
 
 fn confirm<D: Display>(noninteractive: bool, message: D) -> Result<bool> {
     Ok(noninteractive || ask(message)?)
 }
 
+// Prompt before removing a target like rm(1).
+// Write-protected files are always asked about unless noninteractive.
+// Files with uncommitted changes in git need a typed "yes", and are refused if noninteractive
+// unless --force is given.
+fn confirm_target<D: Display>(
+    action: D,
+    target: &Filey,
+    file_type: &FileTypes,
+    options: &Options,
+) -> Result<bool> {
+    if options.noninteractive() && options.force() {
+        return Ok(true);
+    }
+    let changes = match file_type {
+        FileTypes::Symlink => None,
+        _ => git::changes(target.path()).filter(|c| !c.is_empty()),
+    };
+    // Ctrl-C kills git too, and then there are no changes to tell, so the item is skipped.
+    if interrupted() {
+        return Ok(false);
+    }
+    if let Some(changes) = changes {
+        if options.noninteractive() {
+            return Err(UncommittedChanges {
+                path: target.to_string(),
+            });
+        }
+        show_changes(&changes);
+        return ask_yes(format!(
+            "{} {} '{}' with uncommitted changes in git? Type 'yes' to confirm: ",
+            action, file_type, target
+        ));
+    }
+    if options.noninteractive() {
+        Ok(true)
+    } else if *file_type != FileTypes::Symlink && is_write_protected(target) {
+        ask(format!(
+            "{} write-protected {} '{}'? [y/N] ",
+            action, file_type, target
+        ))
+    } else if options.interactive_once() {
+        Ok(true)
+    } else {
+        ask(format!("{} {} '{}'? [y/N] ", action, file_type, target))
+    }
+}
+
+const SHOW_CHANGES_LIMIT: usize = 10;
+
+fn show_changes(changes: &Changes) {
+    let files: Vec<String> = changes
+        .modified()
+        .iter()
+        .map(|p| format!("    {} {}", "modified:".yellow(), p.display()))
+        .chain(
+            changes
+                .untracked()
+                .iter()
+                .map(|p| format!("    {} {}", "untracked:".red(), p.display())),
+        )
+        .collect();
+    for i in files.iter().take(SHOW_CHANGES_LIMIT) {
+        println!("{}", i);
+    }
+    if files.len() > SHOW_CHANGES_LIMIT {
+        println!("    ... and {} more", files.len() - SHOW_CHANGES_LIMIT);
+    }
+}
+
+// Prompt once before removing more than three files or any directory(-I).
+fn confirm_once<D: Display>(action: D, path: &[String], options: &Options) -> Result<bool> {
+    if asks_once(path, options) {
+        ask(format!("{} {} arguments? [y/N] ", action, path.len()))
+    } else {
+        Ok(true)
+    }
+}
+
+// Returns true if -I prompts once for the files and directories.
+pub fn asks_once(path: &[String], options: &Options) -> bool {
+    if options.noninteractive() || !options.interactive_once() {
+        return false;
+    }
+    path.len() > 3
+        || path
+            .iter()
+            .any(|p| Filey::new(p).file_type() == Some(FileTypes::Directory))
+}
+
+// Directories require --recursive, or --dir if they are empty.
+fn check_directory(target: &Filey, file_type: &FileTypes, options: &Options) -> Result<()> {
+    if *file_type != FileTypes::Directory || options.recursive() {
+        return Ok(());
+    }
+    if !options.dir() {
+        return Err(IsADirectory {
+            path: target.to_string(),
+        });
+    }
+    let is_empty = read_dir(target.path())
+        .map_err(|e| e.into())
+        .map_err(WrmError)?
+        .next()
+        .is_none();
+    if is_empty {
+        Ok(())
+    } else {
+        Err(DirectoryNotEmpty {
+            path: target.to_string(),
+        })
+    }
+}
+
+// Files in trash go through purging instead of being trashed again, and the trash root,
+// other files in it and its ancestors are refused.
+// Returns true if the target was handled here.
+fn guard_trash(
+    trash: &Trash,
+    hooks: &Hooks,
+    target: &Filey,
+    options: &Options,
+    report: &mut Report,
+) -> Result<bool> {
+    if let Some(file_info) = trash.entry_at(target.path())? {
+        purge_entry(trash, hooks, &file_info, options, report)?;
+        trash.check()?;
+        return Ok(true);
+    }
+    if let Err(e) = trash.check_target(target.path()) {
+        report.fail(target, &e);
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+fn is_write_protected(target: &Filey) -> bool {
+    match CString::new(target.path().as_os_str().as_bytes()) {
+        Ok(path) => unsafe { libc::access(path.as_ptr(), libc::W_OK) != 0 },
+        Err(_) => false,
+    }
+}
+
 // Move files or directories to trash(~/.config/wrm/trash)
-pub fn remove(
-    path: Vec<String>,
-    wrm_config_dir: &String,
-    noninteractive: bool,
-    quiet: bool,
-) -> Result<()> {
-    for i in path {
+pub fn remove(path: Vec<String>, wrm_path: &WrmPath, options: &Options) -> Result<Report> {
+    if !confirm_once("Remove".red().bold(), &path, options)? {
+        show_message(options.quiet(), "Canceled");
+        return Ok(Report::canceled(path.len()));
+    }
+    let mut report = Report::new();
+    let trash = Trash::new(wrm_path)?;
+    let hooks = read_hooks(&trash)?;
+    for (n, i) in path.iter().enumerate() {
+        if stopped(&mut report, &path[n..]) {
+            break;
+        }
         let target = absolutize(i)?;
+        if guard_trash(&trash, &hooks, &target, options, &mut report)? {
+            continue;
+        }
         let file_type = if let Some(t) = target.file_type() {
             t
         } else {
-            eprintln!(
-                "error: {}",
+            report.fail(
+                &target,
                 NotFoundError {
-                    path: target.to_string()
-                }
+                    path: target.to_string(),
+                },
             );
             continue;
         };
-        if confirm(
-            noninteractive,
-            format!(
-                "{} {} '{}'? [y/N] ",
-                "Remove".red().bold(),
-                file_type,
-                &target,
-            ),
-        )? {
-            if file_type == FileTypes::Symlink {
-                if let Err(e) = target.remove() {
-                    eprintln!("error: {}", e);
-                    continue;
-                }
-                show_message(
-                    quiet,
-                    format!("{} {} '{}'", "Removed".green().bold(), file_type, &target),
-                );
+        if let Err(e) = check_directory(&target, &file_type, options) {
+            report.fail(&target, &e);
+            continue;
+        }
+        let operation = if file_type == FileTypes::Symlink {
+            Operation::Delete
+        } else {
+            Operation::Trash
+        };
+        let mut record = Record::new(operation, target.path(), size(target.path()));
+        let confirmed = match confirm_target("Remove".red().bold(), &target, &file_type, options) {
+            Ok(c) => c,
+            Err(e) => {
+                report.fail(&target, &e);
+                write_log(&trash, record.set_error(e));
+                continue;
+            }
+        };
+        if confirmed {
+            let mut event = Event::new(Action::Remove, vec![target.to_string()]);
+            event.set_file_type(file_type);
+            if file_type != FileTypes::Symlink {
+                let mut file_info = FileInfo::new(target.path(), wrm_path)?;
+                file_info.set_note(options.note().cloned());
+                event.set_files(vec![file_info]);
+            }
+            if let Err(e) = hooks.pre(&mut event) {
+                report.fail(&target, &e);
+                write_log(&trash, record.set_error(e));
+                continue;
+            }
+            // Symbolic links are deleted like rm(1), not moved to trash.
+            let result = if file_type == FileTypes::Symlink {
+                trash.delete(target.path())
             } else {
-                if let Err(e) = target.clone().move_to(format!("{}/trash/", wrm_config_dir)) {
-                    eprintln!("error: {}", e);
-                    continue;
-                }
-                add_file_info(
-                    wrm_config_dir,
-                    &FileInfo::new(target.path(), wrm_config_dir)?,
-                )?;
-                show_message(
-                    quiet,
-                    format!("{} {} '{}'", "Removed".green().bold(), file_type, &target),
-                );
+                trash
+                    .put(target.path(), options.note().cloned())
+                    .map(|file_info| {
+                        event.set_files(vec![file_info]);
+                    })
+            };
+            if let Err(e) = result {
+                report.fail(&target, &e);
+                continue;
             }
+            show_message(
+                options.quiet(),
+                format!("{} {} '{}'", "Removed".green().bold(), file_type, &target),
+            );
+            report.succeed();
+            run_post_hook(&hooks, &mut event);
         } else {
-            show_message(quiet, "Canceled");
+            write_log(&trash, record.set_outcome(Outcome::Canceled));
+            report.cancel();
+            show_message(options.quiet(), "Canceled");
         }
-        check(wrm_config_dir)?;
+        trash.check()?;
     }
-    Ok(())
+    Ok(report)
+}
+
+// Append a record of an action which wasn't done to the audit log.
+// wrm goes on even if the log can't be written.
+fn write_log(trash: &Trash, record: &Record) {
+    if let Err(e) = trash.log(record) {
+        eprintln!("error: {}", e);
+    }
+}
+
+fn read_hooks(trash: &Trash) -> Result<Hooks> {
+    Hooks::read(trash.path().hooks())
 }
 
-fn add_file_info(wrm_config_dir: &String, file_info: &FileInfo) -> Result<()> {
-    let path_to_file_list = format!("{}/list.json", wrm_config_dir);
-    FileList::read(&path_to_file_list)?
-        .add(file_info)
-        .write(&path_to_file_list)
+// A failing post hook can't undo the action, so just report it.
+fn run_post_hook(hooks: &Hooks, event: &mut Event) {
+    if let Err(e) = hooks.post(event) {
+        eprintln!("error: {}", e);
+    }
 }
 
 fn show_message<D: Display>(quiet: bool, message: D) {
@@ -101,148 +332,962 @@ fn show_message<D: Display>(quiet: bool, message: D) {
     }
 }
 
+// Pack files and directories trashed before the time into compressed archives in trash
+// Archived ones are listed, shown and restored like the others.
+pub fn compress(wrm_path: &WrmPath, before: &DateTime<Local>, options: &Options) -> Result<Report> {
+    let mut report = Report::new();
+    let trash = Trash::new(wrm_path)?;
+    let old: Vec<FileInfo> = trash
+        .list()?
+        .files()
+        .iter()
+        .filter(|i| i.archived().is_none() && i.deleted_at().map(|t| t < before).unwrap_or(true))
+        .cloned()
+        .collect();
+    if old.is_empty() {
+        eprintln!("There are no files or directories in trash to compress");
+    }
+    let paths: Vec<&String> = old.iter().map(|i| i.path_trash()).collect();
+    for (n, i) in old.iter().enumerate() {
+        if stopped(&mut report, &paths[n..]) {
+            break;
+        }
+        match trash.pack(i) {
+            Ok(packed) => {
+                show_message(
+                    options.quiet(),
+                    format!(
+                        "{} '{}' ({} -> {})",
+                        "Compressed".green().bold(),
+                        packed.path_trash(),
+                        human_size(packed.archived().map(|a| a.size()).unwrap_or_default()),
+                        human_size(size(packed.stored_path()))
+                    ),
+                );
+                report.succeed();
+            }
+            Err(e) => {
+                report.fail(i.path_trash(), &e);
+            }
+        }
+    }
+    Ok(report)
+}
+
 // Delete all files and directories in trash permanently
-pub fn clean(wrm_config_dir: &String, noninteractive: bool, quiet: bool) -> Result<()> {
-    let file_list = format!("{}/list.json", wrm_config_dir);
-    if FileList::read(file_list)?.files().is_empty() {
+// Pinned files and directories need an extra confirmation, and are kept if noninteractive.
+pub fn clean(wrm_path: &WrmPath, options: &Options) -> Result<Report> {
+    let mut report = Report::new();
+    let trash = Trash::new(wrm_path)?;
+    let file_list = trash.list()?;
+    if file_list.files().is_empty() {
         eprintln!("There are no files or directories in trash");
     } else {
-        if !noninteractive {
-            list(wrm_config_dir)?;
+        if !options.noninteractive() {
+            draw_list(&file_list, current_uid())?;
         }
         if confirm(
-            noninteractive,
+            options.noninteractive(),
             format!(
                 "{} these files and directories? [y/N] ",
                 "Delete".red().bold()
             ),
         )? {
-            remove!(wrm_config_dir);
-            create_dir!(wrm_config_dir);
-            show_message(quiet, format!("{} trash", "Cleaned".green().bold()));
+            let hooks = read_hooks(&trash)?;
+            let mut event = Event::new(Action::Clean, trash_paths(&file_list));
+            event.set_files(file_list.files().clone());
+            if let Err(e) = hooks.pre(&mut event) {
+                report.fail(wrm_path.trash().display(), &e);
+                for i in file_list.files() {
+                    let mut record = Record::new(Operation::Clean, i.path(), i.size());
+                    record.set_path_trash(i.path_trash());
+                    write_log(&trash, record.set_error(&e));
+                }
+                return Ok(report);
+            }
+            let pinned = file_list.pinned().files().len();
+            let include_pinned = pinned == 0
+                || (!options.noninteractive()
+                    && ask(format!(
+                        "{} {} pinned files and directories too? [y/N] ",
+                        "Delete".red().bold(),
+                        pinned
+                    ))?);
+            trash.empty(include_pinned)?;
+            if include_pinned {
+                show_message(
+                    options.quiet(),
+                    format!("{} trash", "Cleaned".green().bold()),
+                );
+            } else {
+                show_message(
+                    options.quiet(),
+                    format!(
+                        "{} trash except {} pinned files and directories",
+                        "Cleaned".green().bold(),
+                        pinned
+                    ),
+                );
+            }
+            report.succeed();
+            run_post_hook(&hooks, &mut event);
         } else {
-            show_message(quiet, "Canceled");
+            report.cancel();
+            show_message(options.quiet(), "Canceled");
         }
     }
-    Ok(())
+    Ok(report)
 }
 
-// Delete files or directories
-pub fn delete(
+// Pin or unpin files or directories in trash so that purging never deletes them
+pub fn pin(
     path: Vec<String>,
-    wrm_config_dir: &String,
-    noninteractive: bool,
-    quiet: bool,
-) -> Result<()> {
-    for i in path {
+    wrm_path: &WrmPath,
+    value: bool,
+    options: &Options,
+) -> Result<Report> {
+    let mut report = Report::new();
+    let trash = Trash::new(wrm_path)?;
+    for (n, i) in path.iter().enumerate() {
+        if stopped(&mut report, &path[n..]) {
+            break;
+        }
+        let file_list = trash.list()?;
+        if let Some(file_info) = find_entry(&file_list, i)? {
+            trash.set_pinned(file_info, value)?;
+            show_message(
+                options.quiet(),
+                format!(
+                    "{} '{}'",
+                    if value { "Pinned" } else { "Unpinned" }.green().bold(),
+                    file_info.path_trash()
+                ),
+            );
+            report.succeed();
+        } else {
+            report.fail(i, NotFoundError { path: i.clone() });
+        }
+    }
+    Ok(report)
+}
+
+// Delete files or directories
+pub fn delete(path: Vec<String>, wrm_path: &WrmPath, options: &Options) -> Result<Report> {
+    if !confirm_once("Delete".red().bold(), &path, options)? {
+        show_message(options.quiet(), "Canceled");
+        return Ok(Report::canceled(path.len()));
+    }
+    let mut report = Report::new();
+    let trash = Trash::new(wrm_path)?;
+    let hooks = read_hooks(&trash)?;
+    for (n, i) in path.iter().enumerate() {
+        if stopped(&mut report, &path[n..]) {
+            break;
+        }
         let target = absolutize(i)?;
+        if guard_trash(&trash, &hooks, &target, options, &mut report)? {
+            continue;
+        }
         let file_type = if let Some(t) = target.file_type() {
             t
         } else {
-            eprintln!(
-                "error: {}",
+            report.fail(
+                &target,
                 NotFoundError {
-                    path: target.to_string()
-                }
+                    path: target.to_string(),
+                },
             );
             continue;
         };
+        if let Err(e) = check_directory(&target, &file_type, options) {
+            report.fail(&target, &e);
+            continue;
+        }
+        let mut record = Record::new(Operation::Delete, target.path(), size(target.path()));
+        let confirmed = match confirm_target("Delete".red().bold(), &target, &file_type, options) {
+            Ok(c) => c,
+            Err(e) => {
+                report.fail(&target, &e);
+                write_log(&trash, record.set_error(e));
+                continue;
+            }
+        };
+        if confirmed {
+            let mut event = Event::new(Action::Delete, vec![target.to_string()]);
+            event.set_file_type(file_type);
+            if let Err(e) = hooks.pre(&mut event) {
+                report.fail(&target, &e);
+                write_log(&trash, record.set_error(e));
+                continue;
+            }
+            if let Err(e) = trash.delete(target.path()) {
+                report.fail(&target, &e);
+                continue;
+            }
+            show_message(
+                options.quiet(),
+                format!("{} {} '{}'", "Deleted".green().bold(), file_type, &target),
+            );
+            report.succeed();
+            run_post_hook(&hooks, &mut event);
+        } else {
+            write_log(&trash, record.set_outcome(Outcome::Canceled));
+            report.cancel();
+            show_message(options.quiet(), "Canceled");
+        }
+        trash.check()?;
+    }
+    Ok(report)
+}
+
+// Restore files or directories in trash to where they came from, or to the given destination
+// Files in trash can be given by their paths in trash or their original paths.
+// If a path was trashed several times, the given version is restored.
+pub fn restore(
+    path: Vec<String>,
+    wrm_path: &WrmPath,
+    to: Option<&String>,
+    version: &Version,
+    options: &Options,
+) -> Result<Report> {
+    let to = to.map(Path::new);
+    let mut report = Report::new();
+    let trash = Trash::new(wrm_path)?;
+    let hooks = read_hooks(&trash)?;
+    for (n, i) in path.iter().enumerate() {
+        if stopped(&mut report, &path[n..]) {
+            break;
+        }
+        let given = absolutize(i)?;
+        let file_list = trash.list()?;
+        let file_info = if let Some(f) = file_list.find_version(&given.to_string(), version) {
+            f
+        } else {
+            report.fail(
+                &given,
+                NotFoundError {
+                    path: given.to_string(),
+                },
+            );
+            continue;
+        };
+        let target = absolutize(file_info.path_trash())?;
+        let file_type = if let Some(t) = entry_type(file_info) {
+            t
+        } else {
+            report.fail(
+                &target,
+                NotFoundError {
+                    path: target.to_string(),
+                },
+            );
+            continue;
+        };
+        let destination = match Trash::destination(file_info, to) {
+            Ok(d) => Filey::new(d),
+            Err(e) => {
+                report.fail(&target, &e);
+                continue;
+            }
+        };
+        let mut record = Record::new(Operation::Restore, destination.path(), file_info.size());
+        record.set_path_trash(target.path());
         if confirm(
-            noninteractive,
+            options.noninteractive(),
             format!(
-                "{} {} '{}'? [y/N] ",
-                "Delete".red().bold(),
+                "{} {} '{}' to '{}'? [y/N] ",
+                "Restore".red().bold(),
                 file_type,
-                &target
+                &target,
+                &destination
             ),
         )? {
-            if let Err(e) = target.remove() {
-                eprintln!("error {}", e);
+            if destination.exists() {
+                if options.noninteractive() {
+                    let e = AlreadyExists {
+                        path: destination.to_string(),
+                    };
+                    report.fail(&given, &e);
+                    write_log(&trash, record.set_error(e));
+                    continue;
+                }
+                if !ask(format!(
+                    "{} '{}'(it is moved to trash)? [y/N] ",
+                    "Overwrite".red().bold(),
+                    &destination
+                ))? {
+                    write_log(&trash, record.set_outcome(Outcome::Canceled));
+                    report.cancel();
+                    show_message(options.quiet(), "Canceled");
+                    continue;
+                }
+            }
+            let mut event = Event::new(Action::Restore, vec![destination.to_string()]);
+            event
+                .set_file_type(file_type)
+                .set_files(vec![file_info.clone()]);
+            if let Err(e) = hooks.pre(&mut event) {
+                report.fail(&given, &e);
+                write_log(&trash, record.set_error(e));
+                continue;
+            }
+            if let Err(e) = trash.restore(file_info, to, true) {
+                report.fail(&given, &e);
                 continue;
             }
             show_message(
-                quiet,
-                format!("{} {} '{}'", "Deleted".green().bold(), file_type, &target),
+                options.quiet(),
+                format!(
+                    "{} {} '{}' to '{}'",
+                    "Restored".green().bold(),
+                    file_type,
+                    file_info.path_trash(),
+                    &destination
+                ),
             );
+            report.succeed();
+            run_post_hook(&hooks, &mut event);
         } else {
-            show_message(quiet, "Canceled");
+            write_log(&trash, record.set_outcome(Outcome::Canceled));
+            report.cancel();
+            show_message(options.quiet(), "Canceled");
         }
-        check(wrm_config_dir)?;
+        trash.check()?;
     }
-    Ok(())
+    Ok(report)
 }
 
-// Restore files or directories in trash to where they came from
-pub fn restore(
-    path: Vec<String>,
-    wrm_config_dir: &String,
-    noninteractive: bool,
-    quiet: bool,
-) -> Result<()> {
+// Returns paths in trash of files which came from the directory, or were trashed in the window
+// If a path was trashed several times, only the latest version is picked.
+pub fn restorable(
+    wrm_path: &WrmPath,
+    from: Option<&String>,
+    since: Option<&DateTime<Local>>,
+    until: Option<&DateTime<Local>>,
+) -> Result<Vec<String>> {
+    let mut file_list = Trash::new(wrm_path)?.list()?;
+    if let Some(dir) = from {
+        file_list = file_list.under(absolutize(dir)?.path());
+    }
+    if since.is_some() || until.is_some() {
+        file_list = file_list.deleted_between(since, until);
+    }
+    let path = trash_paths(&file_list.latest());
+    if path.is_empty() {
+        eprintln!("There are no matching files or directories in trash");
+    }
+    Ok(path)
+}
+
+// Show every trashed version of the given paths
+pub fn versions(path: Vec<String>, wrm_path: &WrmPath) -> Result<()> {
+    let file_list = Trash::new(wrm_path)?.list()?;
     for i in path {
         let given = absolutize(i)?;
-        for j in FileList::read(format!("{}/list.json", wrm_config_dir))?.files() {
-            let original = absolutize(j.path())?;
-            let mut target = absolutize(j.path_trash())?;
-            let file_type = target.file_type().ok_or_else(|| NotFoundError {
-                path: target.to_string(),
-            })?;
-            if given.path() == target.path() {
-                if confirm(
-                    noninteractive,
-                    format!(
-                        "{} {} '{}' to '{}'? [y/N] ",
-                        "Restore".red().bold(),
-                        file_type,
-                        &target,
-                        &original
-                    ),
-                )? {
-                    if let Err(e) = target.move_to(&original) {
-                        eprintln!("error: {}", e);
-                        break;
-                    }
-                    show_message(
-                        quiet,
-                        format!(
-                            "{} {} '{}' to '{}'",
-                            "Restored".green().bold(),
-                            file_type,
-                            &target,
-                            &original
-                        ),
-                    );
-                } else {
-                    show_message(quiet, "Canceled");
-                }
-                break;
+        let versions = file_list.versions(&given.to_string());
+        if versions.is_empty() {
+            eprintln!("There are no trashed versions of '{}'", given);
+            continue;
+        }
+        println!("{}", versions[0].path().bold());
+        for (n, j) in versions.iter().enumerate() {
+            println!(
+                "{:>4}  {}  {:>6}  {}",
+                n + 1,
+                j.deleted_at()
+                    .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+                    .unwrap_or_else(|| "-".repeat(19)),
+                human_size(j.size()),
+                j.path_trash()
+            );
+        }
+    }
+    Ok(())
+}
+
+// Show files or directories in trash without restoring them
+// Files are printed, directories are drawn as trees with sizes and symlinks show their targets.
+pub fn show(path: Vec<String>, wrm_path: &WrmPath) -> Result<Report> {
+    let mut report = Report::new();
+    let trash = Trash::new(wrm_path)?;
+    let file_list = trash.list()?;
+    for (n, i) in path.iter().enumerate() {
+        if stopped(&mut report, &path[n..]) {
+            break;
+        }
+        let file_info = if let Some(f) = find_entry(&file_list, i)? {
+            f
+        } else {
+            report.fail(i, NotFoundError { path: i.clone() });
+            continue;
+        };
+        let target = absolutize(file_info.path_trash())?;
+        let file_type = if let Some(t) = entry_type(file_info) {
+            t
+        } else {
+            report.fail(
+                &target,
+                NotFoundError {
+                    path: target.to_string(),
+                },
+            );
+            continue;
+        };
+        println!("{}", colorize(file_info)?);
+        if file_info.archived().is_some() {
+            show_archived(file_info, file_type)?;
+        } else {
+            show_entry(target.path(), file_type)?;
+        }
+        report.succeed();
+    }
+    Ok(report)
+}
+
+fn show_entry(path: &Path, file_type: FileTypes) -> Result<()> {
+    match file_type {
+        FileTypes::File => show_file(path),
+        FileTypes::Directory => show_tree(path),
+        FileTypes::Symlink => {
+            println!(
+                "-> {}",
+                read_link(path)
+                    .map_err(|e| e.into())
+                    .map_err(WrmError)?
+                    .display()
+            );
+            Ok(())
+        }
+    }
+}
+
+// Files in trash can be given by their paths in trash, their original paths or their names.
+fn find_entry<'a>(file_list: &'a FileList, given: &str) -> Result<Option<&'a FileInfo>> {
+    let absolutized = absolutize(given)?.to_string();
+    Ok(file_list
+        .find(&absolutized)
+        .or_else(|| file_list.find_by_name(given)))
+}
+
+const SHOW_LIMIT: usize = 64 * 1024;
+
+// Print a text file, or a hex dump of a binary file.
+fn show_file(path: &Path) -> Result<()> {
+    let f = File::open(path).map_err(|e| e.into()).map_err(WrmError)?;
+    show_contents(f, size(path))
+}
+
+// Print a text file, or a hex dump of a binary file, up to SHOW_LIMIT bytes.
+fn show_contents<R: Read>(reader: R, total: u64) -> Result<()> {
+    let mut buffer = vec![];
+    reader
+        .take(SHOW_LIMIT as u64 + 1)
+        .read_to_end(&mut buffer)
+        .map_err(|e| e.into())
+        .map_err(WrmError)?;
+    let truncated = buffer.len() > SHOW_LIMIT;
+    buffer.truncate(SHOW_LIMIT);
+    match std::str::from_utf8(&buffer) {
+        Ok(text) if !text.contains('\0') => {
+            print!("{}", text);
+            if !text.ends_with('\n') {
+                println!();
+            }
+        }
+        // A multibyte character may be cut at the limit.
+        Err(e) if truncated && e.error_len().is_none() => {
+            println!("{}", String::from_utf8_lossy(&buffer[..e.valid_up_to()]));
+        }
+        _ => {
+            println!("{}", "binary file".italic());
+            for (n, chunk) in buffer.chunks(16).take(32).enumerate() {
+                let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+                let ascii: String = chunk
+                    .iter()
+                    .map(|b| {
+                        if b.is_ascii_graphic() || *b == b' ' {
+                            *b as char
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect();
+                println!("{:08x}  {:<47}  {}", n * 16, hex.join(" "), ascii);
             }
         }
-        check(wrm_config_dir)?;
     }
+    if truncated {
+        println!("{}", format!("... ({} bytes in total)", total).italic());
+    }
+    Ok(())
+}
+
+// An entry of a directory drawn by draw_tree. The path is relative to the directory.
+struct Node {
+    path: PathBuf,
+    is_dir: bool,
+    size: u64,
+    link: Option<String>,
+}
+
+// Draw a directory as a tree with sizes.
+fn show_tree(path: &Path) -> Result<()> {
+    let nodes = WalkDir::new(path)
+        .min_depth(1)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .map(|entry| Node {
+            path: entry
+                .path()
+                .strip_prefix(path)
+                .unwrap_or(entry.path())
+                .to_path_buf(),
+            is_dir: entry.file_type().is_dir(),
+            size: size(entry.path()),
+            link: if entry.file_type().is_symlink() {
+                Some(
+                    read_link(entry.path())
+                        .map(|p| p.display().to_string())
+                        .unwrap_or_default(),
+                )
+            } else {
+                None
+            },
+        })
+        .collect();
+    draw_tree(nodes);
+    Ok(())
+}
+
+// Nodes are sorted by path, which puts each directory before its entries.
+fn draw_tree(mut nodes: Vec<Node>) {
+    nodes.sort_by(|a, b| a.path.cmp(&b.path));
+    for node in nodes {
+        let name = node
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let indent = "    ".repeat(node.path.components().count() - 1);
+        if let Some(link) = node.link {
+            println!("{}{} -> {}", indent, name.cyan(), link);
+        } else if node.is_dir {
+            println!("{}{}/ ({})", indent, name.blue(), human_size(node.size));
+        } else {
+            println!("{}{} ({})", indent, name, human_size(node.size));
+        }
+    }
+}
+
+// Archived entries are read from their archives without unpacking them anywhere.
+fn show_archived(file_info: &FileInfo, file_type: FileTypes) -> Result<()> {
+    let f = File::open(file_info.archive_path())
+        .map_err(|e| e.into())
+        .map_err(WrmError)?;
+    let mut archive = Archive::new(GzDecoder::new(f));
+    let mut nodes: Vec<Node> = vec![];
+    // Sizes of directories are the totals of the files under them.
+    let mut dir_sizes: BTreeMap<PathBuf, u64> = BTreeMap::new();
+    for entry in archive.entries().map_err(|e| e.into()).map_err(WrmError)? {
+        let entry = entry.map_err(|e| e.into()).map_err(WrmError)?;
+        let header = entry.header();
+        let kind = header.entry_type();
+        if file_type != FileTypes::Directory {
+            // A file or a symlink is the only entry.
+            return match header.link_name().ok().flatten() {
+                Some(link) if kind.is_symlink() => {
+                    println!("-> {}", link.display());
+                    Ok(())
+                }
+                _ => {
+                    let total = entry.size();
+                    show_contents(entry, total)
+                }
+            };
+        }
+        // Entries are named from the directory itself, which isn't drawn.
+        let path: PathBuf = entry
+            .path()
+            .map_err(|e| e.into())
+            .map_err(WrmError)?
+            .components()
+            .skip(1)
+            .collect();
+        if path.as_os_str().is_empty() {
+            continue;
+        }
+        let link = if kind.is_symlink() {
+            Some(
+                header
+                    .link_name()
+                    .ok()
+                    .flatten()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_default(),
+            )
+        } else {
+            None
+        };
+        let size = if kind.is_file() { entry.size() } else { 0 };
+        for dir in path.ancestors().skip(1) {
+            *dir_sizes.entry(dir.to_path_buf()).or_default() += size;
+        }
+        nodes.push(Node {
+            path,
+            is_dir: kind.is_dir(),
+            size,
+            link,
+        });
+    }
+    for node in nodes.iter_mut().filter(|n| n.is_dir) {
+        node.size = dir_sizes.get(&node.path).copied().unwrap_or(0);
+    }
+    draw_tree(nodes);
     Ok(())
 }
 
 // List all files and directories in trash
-pub fn list(wrm_config_dir: &String) -> Result<()> {
-    let file_list = FileList::read(format!("{}/list.json", wrm_config_dir))?;
-    if file_list.files().is_empty() {
+pub fn list(wrm_path: &WrmPath, search: Option<&String>, json: bool) -> Result<()> {
+    let mut file_list = Trash::new(wrm_path)?.list()?;
+    if let Some(text) = search {
+        file_list = file_list.search(text);
+    }
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&file_list)
+                .map_err(|e| e.into())
+                .map_err(WrmError)?
+        );
+    } else if file_list.files().is_empty() {
         eprintln!("There are no files or directories in trash");
     } else {
-        draw_list(&file_list)?;
+        draw_list(&file_list, current_uid())?;
     }
     Ok(())
 }
 
-fn draw_list(file_list: &FileList) -> Result<()> {
+// List files and directories in the trash of every user in ~/.config/wrm
+// Lists which can't be read, usually because wrm isn't run by root, are reported as failures.
+pub fn list_all_users(search: Option<&String>, json: bool) -> Result<Report> {
+    let mut report = Report::new();
+    let mut lists = BTreeMap::new();
+    let mut homes = BTreeSet::new();
+    for (user, uid, home) in users() {
+        let wrm_path = WrmPath::new(home.join(".config/wrm"));
+        if !homes.insert(home) || !wrm_path.list().exists() {
+            continue;
+        }
+        match FileList::read(wrm_path.list()) {
+            Ok(file_list) => {
+                let file_list = match search {
+                    Some(text) => file_list.search(text),
+                    None => file_list,
+                };
+                lists.insert(user, (uid, wrm_path, file_list));
+                report.succeed();
+            }
+            Err(e) => {
+                report.fail(wrm_path.list().display(), e);
+            }
+        }
+    }
+    if json {
+        let lists: BTreeMap<&String, &FileList> =
+            lists.iter().map(|(u, (_, _, l))| (u, l)).collect();
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&lists)
+                .map_err(|e| e.into())
+                .map_err(WrmError)?
+        );
+        return Ok(report);
+    }
+    for (user, (uid, wrm_path, file_list)) in &lists {
+        println!("{} ({})", user.bold(), wrm_path.dir().display());
+        if file_list.files().is_empty() {
+            println!("{}", "no files or directories".italic());
+        } else {
+            draw_list(file_list, *uid)?;
+        }
+    }
+    Ok(report)
+}
+
+// Files owned by someone else than the user of the trash, e.g. trashed by root, show their owners.
+fn draw_list(file_list: &FileList, uid: u32) -> Result<()> {
     for i in file_list.files() {
-        println!("{}", colorize(i.path_trash(), i.path())?);
+        let mut line = colorize(i)?;
+        if let Some(owner) = i.owner().filter(|o| o.uid() != uid) {
+            let name = user_name(owner.uid()).unwrap_or_else(|| owner.uid().to_string());
+            line = format!("{} [owner: {}]", line, name);
+        }
+        if i.pinned() {
+            line = format!("{} {}", line, "[pinned]".bold());
+        }
+        if let Some(note) = i.note() {
+            line = format!("{} {}", line, format!("- {}", note).italic());
+        }
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+const STATS_LARGEST: usize = 10;
+
+// Summarize the trash: the total, the largest entries, and usage by original directory,
+// by age and by filesystem
+pub fn stats(wrm_path: &WrmPath, json: bool) -> Result<()> {
+    let stats = Stats::new(&Trash::new(wrm_path)?.list()?, STATS_LARGEST);
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&stats)
+                .map_err(|e| e.into())
+                .map_err(WrmError)?
+        );
+        return Ok(());
+    }
+    let row = |usage: &Usage, name: &str| {
+        println!(
+            "    {:>6}  {:>5}  {}",
+            human_size(usage.size()),
+            usage.count(),
+            name
+        );
+    };
+    println!(
+        "{} {} files and directories, {}",
+        "Total".bold(),
+        stats.total().count(),
+        human_size(stats.total().size())
+    );
+    if stats.total().count() == 0 {
+        return Ok(());
+    }
+    println!("{}", "Largest".bold());
+    for i in stats.largest() {
+        println!(
+            "    {:>6}  {}",
+            human_size(i.size()),
+            colorize(i.file_info())?
+        );
+    }
+    println!("{}", "By original directory".bold());
+    let mut parents: Vec<(&String, &Usage)> = stats.parents().iter().collect();
+    parents.sort_by_key(|(_, usage)| std::cmp::Reverse(usage.size()));
+    for (parent, usage) in parents {
+        row(usage, parent);
+    }
+    println!("{}", "By age".bold());
+    row(stats.ages().today(), "today");
+    row(stats.ages().this_week(), "this week");
+    row(stats.ages().older(), "older");
+    println!("{}", "By filesystem".bold());
+    for (mount_point, usage) in stats.filesystems() {
+        row(usage, mount_point);
+    }
+    let trash_filesystem = mount_point(wrm_path.trash());
+    println!(
+        "{} {} is on {} ({} available)",
+        "Trash".bold(),
+        wrm_path.trash().display(),
+        trash_filesystem.display(),
+        available(&trash_filesystem)
+            .map(human_size)
+            .unwrap_or_else(|| "-".to_string())
+    );
+    Ok(())
+}
+
+// Returns paths in trash of files whose note contains the given text
+pub fn search(wrm_path: &WrmPath, text: &str) -> Result<Vec<String>> {
+    let file_list = Trash::new(wrm_path)?.list()?;
+    Ok(trash_paths(&file_list.search(text)))
+}
+
+// Find files and directories in trash by their original paths and contents
+// Paths in trash are printed first so that they can be given to --restore or --clean.
+pub fn find(wrm_path: &WrmPath, finder: &Finder, json: bool) -> Result<()> {
+    let file_list = Trash::new(wrm_path)?.list()?;
+    let found: Vec<Found> = file_list
+        .files()
+        .iter()
+        .filter_map(|i| finder.find(i))
+        .collect();
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&found)
+                .map_err(|e| e.into())
+                .map_err(WrmError)?
+        );
+        return Ok(());
+    }
+    if found.is_empty() {
+        eprintln!("There are no matching files or directories in trash");
+    }
+    for i in found {
+        let file_info = i.file_info();
+        println!("{} ({})", file_info.path_trash(), file_info.path());
+        for line in i.lines() {
+            println!(
+                "    {}:{}: {}",
+                line.path().display().to_string().magenta(),
+                line.number().to_string().green(),
+                line.text()
+            );
+        }
     }
     Ok(())
 }
 
-fn colorize<P: AsRef<Path>>(path: P, original: P) -> Result<String> {
-    let target = Filey::new(path);
+// Returns paths in trash of files found by the finder
+pub fn found(wrm_path: &WrmPath, finder: &Finder) -> Result<Vec<String>> {
+    let file_list = Trash::new(wrm_path)?.list()?;
+    Ok(file_list
+        .files()
+        .iter()
+        .filter(|i| finder.matches(i))
+        .map(|i| i.path_trash().to_string())
+        .collect())
+}
+
+fn trash_paths(file_list: &FileList) -> Vec<String> {
+    file_list
+        .files()
+        .iter()
+        .map(|i| i.path_trash().to_string())
+        .collect()
+}
+
+// Delete the given files or directories in trash permanently
+// Pinned files and directories are skipped.
+pub fn purge(path: Vec<String>, wrm_path: &WrmPath, options: &Options) -> Result<Report> {
+    let mut report = Report::new();
+    if path.is_empty() {
+        eprintln!("There are no matching files or directories in trash");
+        return Ok(report);
+    }
+    let trash = Trash::new(wrm_path)?;
+    let hooks = read_hooks(&trash)?;
+    for (n, i) in path.iter().enumerate() {
+        if stopped(&mut report, &path[n..]) {
+            break;
+        }
+        let target = absolutize(i)?;
+        let file_list = trash.list()?;
+        let file_info = if let Some(f) = file_list.find(&target.to_string()) {
+            f
+        } else {
+            report.fail(
+                &target,
+                NotFoundError {
+                    path: target.to_string(),
+                },
+            );
+            continue;
+        };
+        purge_entry(&trash, &hooks, file_info, options, &mut report)?;
+        trash.check()?;
+    }
+    Ok(report)
+}
+
+// Delete a file or a directory in trash permanently unless it is pinned
+fn purge_entry(
+    trash: &Trash,
+    hooks: &Hooks,
+    file_info: &FileInfo,
+    options: &Options,
+    report: &mut Report,
+) -> Result<()> {
+    let target = absolutize(file_info.path_trash())?;
+    if file_info.pinned() {
+        show_message(
+            options.quiet(),
+            format!("{} pinned '{}'", "Skipped".yellow().bold(), &target),
+        );
+        report.skip();
+        return Ok(());
+    }
+    let file_type = if let Some(t) = entry_type(file_info) {
+        t
+    } else {
+        report.fail(
+            &target,
+            NotFoundError {
+                path: target.to_string(),
+            },
+        );
+        return Ok(());
+    };
+    if confirm(
+        options.noninteractive(),
+        format!(
+            "{} {} '{}' permanently? [y/N] ",
+            "Delete".red().bold(),
+            file_type,
+            &target
+        ),
+    )? {
+        let mut event = Event::new(Action::Clean, vec![target.to_string()]);
+        event
+            .set_file_type(file_type)
+            .set_files(vec![file_info.clone()]);
+        if let Err(e) = hooks.pre(&mut event) {
+            report.fail(&target, &e);
+            let mut record = Record::new(Operation::Purge, file_info.path(), file_info.size());
+            write_log(trash, record.set_path_trash(target.path()).set_error(e));
+            return Ok(());
+        }
+        if let Err(e) = trash.purge(file_info) {
+            report.fail(&target, &e);
+            return Ok(());
+        }
+        show_message(
+            options.quiet(),
+            format!("{} {} '{}'", "Deleted".green().bold(), file_type, &target),
+        );
+        report.succeed();
+        run_post_hook(hooks, &mut event);
+    } else {
+        report.cancel();
+        show_message(options.quiet(), "Canceled");
+    }
+    Ok(())
+}
+
+// Print records of the audit log
+// Records can be filtered by time and by paths(including files under directories).
+pub fn log(
+    path: Vec<String>,
+    wrm_path: &WrmPath,
+    since: Option<&DateTime<Local>>,
+    until: Option<&DateTime<Local>>,
+    json: bool,
+) -> Result<()> {
+    let mut prefixes = vec![];
+    for i in path {
+        prefixes.push(absolutize(i)?.path().to_path_buf());
+    }
+    for record in AuditLog::new(wrm_path.log()).query(&prefixes, since, until)? {
+        if json {
+            println!(
+                "{}",
+                serde_json::to_string(&record)
+                    .map_err(|e| e.into())
+                    .map_err(WrmError)?
+            );
+        } else {
+            println!("{}", record);
+        }
+    }
+    Ok(())
+}
+
+fn colorize(file_info: &FileInfo) -> Result<String> {
+    let target = Filey::new(file_info.path_trash());
     let file_name = target
         .file_name()
         .ok_or_else(|| GetFileNameError {
@@ -250,39 +1295,27 @@ fn colorize<P: AsRef<Path>>(path: P, original: P) -> Result<String> {
         })
         .map_err(|e| e.into())
         .map_err(WrmError)?;
-    match target.file_type().ok_or_else(|| NotFoundError {
+    let file_type = entry_type(file_info).ok_or_else(|| NotFoundError {
         path: target.to_string(),
-    })? {
-        FileTypes::File => Ok(format!(
-            "{} ({}) {}",
-            file_name,
-            original.as_ref().display(),
-            FileTypes::File
-        )),
-        FileTypes::Directory => Ok(format!(
-            "{} ({}) {}",
-            file_name.blue(),
-            original.as_ref().display(),
-            FileTypes::Directory
-        )),
-        FileTypes::Symlink => Ok(format!(
-            "{} ({}) {}",
-            file_name.cyan(),
-            original.as_ref().display(),
-            FileTypes::Symlink
-        )),
-    }
-}
-
-fn check(wrm_config_dir: &String) -> Result<()> {
-    let path_to_file_list = format!("{}/list.json", wrm_config_dir);
-    let file_list = FileList::read(&path_to_file_list)?;
-    for i in file_list.files() {
-        if !absolutize(i.path_trash())?.exists() {
-            file_list.clone().remove(i).write(&path_to_file_list)?;
-        }
+    })?;
+    let file_name = match file_type {
+        FileTypes::File => file_name.normal(),
+        FileTypes::Directory => file_name.blue(),
+        FileTypes::Symlink => file_name.cyan(),
+    };
+    let line = format!("{} ({}) {}", file_name, file_info.path(), file_type);
+    Ok(match file_info.archived() {
+        Some(_) => format!("{} {}", line, "[archived]".dimmed()),
+        None => line,
+    })
+}
+
+// Archived entries keep the type they had before being packed.
+fn entry_type(file_info: &FileInfo) -> Option<FileTypes> {
+    match file_info.archived() {
+        Some(archived) => Some(archived.file_type()),
+        None => Filey::new(file_info.path_trash()).file_type(),
     }
-    Ok(())
 }
 
 fn absolutize<P: AsRef<Path>>(path: P) -> Result<Filey> {
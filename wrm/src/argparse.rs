@@ -1,14 +1,24 @@
 use crate::{
-    actions::{clean, delete, list, remove, restore},
-    Error::WrmError,
-    Result,
+    actions::{
+        clean, compress, delete, find, found, list, list_all_users, log, pin, purge, remove,
+        restorable, restore, search, show, stats, versions,
+    },
+    interrupt::catch,
+    report::Report,
+};
+use chrono::{DateTime, Local};
+use clap::{error::ErrorKind, ArgGroup, CommandFactory, Parser};
+use std::{fmt::Display, path::Path};
+use wrm::{
+    file_list::Version, finder::Finder, utils::parse_time, Error::NotADirectory, Result, Trash,
+    WrmPath,
 };
-use clap::{error::ErrorKind, Command, Parser};
 
 /// wrm
 ///
 /// A file deletion utility
 #[derive(Debug, Parser)]
+#[clap(group(ArgGroup::new("restoring").multiple(true).args(["restore", "restore_from", "restore_since", "restore_between"])))]
 #[clap(name = env!("CARGO_PKG_NAME"), version = env!("CARGO_PKG_VERSION"), author = env!("CARGO_PKG_AUTHORS"), about = env!("CARGO_PKG_DESCRIPTION"), arg_required_else_help = true, verbatim_doc_comment)]
 struct Args {
     file: Option<Vec<String>>,
@@ -21,35 +31,343 @@ struct Args {
     /// List all files and directories in trash.
     #[clap(short, long)]
     list: bool,
+    /// List files and directories in the trash of every user(usually needs root).
+    #[clap(long, requires = "list")]
+    all_users: bool,
+    /// Summarize the trash: total size, largest entries, and usage by original directory,
+    /// by age and by filesystem.
+    #[clap(long, verbatim_doc_comment)]
+    stats: bool,
     /// Restore files or directories in trash to where they came from.
     #[clap(short, long)]
     restore: bool,
+    /// Restore every file and directory in trash which came from DIR or under it.
+    #[clap(long, value_name = "DIR")]
+    restore_from: Option<String>,
+    /// Restore every file and directory in trash which was trashed since TIME.
+    #[clap(long, value_name = "TIME", conflicts_with = "restore_between")]
+    restore_since: Option<String>,
+    /// Restore every file and directory in trash which was trashed between two times.
+    #[clap(long, value_names = ["START", "END"], num_args = 2)]
+    restore_between: Option<Vec<String>>,
+    /// Pack files and directories trashed before TIME(e.g. 30d) into compressed archives in trash.
+    /// Archived ones can still be listed, shown and restored.
+    #[clap(long, value_name = "TIME", verbatim_doc_comment)]
+    compress: Option<String>,
+    /// Print the audit log of destructive operations.
+    /// If files are given, print only records about them or files under them.
+    #[clap(long, verbatim_doc_comment)]
+    log: bool,
+    /// Print only records since TIME(e.g. 2024-01-31, "2024-01-31 12:00", 2h, 7d).
+    #[clap(long, value_name = "TIME", requires = "log")]
+    since: Option<String>,
+    /// Print only records until TIME.
+    #[clap(long, value_name = "TIME", requires = "log")]
+    until: Option<String>,
+    /// Pin files or directories in trash so that purging never deletes them.
+    #[clap(long, conflicts_with = "unpin")]
+    pin: bool,
+    /// Unpin files or directories in trash.
+    #[clap(long)]
+    unpin: bool,
+    /// Attach a note to files or directories moved to trash.
+    #[clap(short = 'm', long = "message", value_name = "NOTE")]
+    note: Option<String>,
+    /// Select files and directories in trash whose note contains TEXT.
+    /// Works with --list, --restore and --clean.
+    #[clap(long, value_name = "TEXT")]
+    search: Option<String>,
+    /// Find files and directories in trash whose original paths match PATTERN(a glob).
+    /// A pattern without '/' is matched against file names.
+    /// Works with --restore and --clean.
+    #[clap(long, value_name = "PATTERN", verbatim_doc_comment)]
+    find: Option<String>,
+    /// Use a regex instead of a glob for --find.
+    #[clap(long, requires = "find")]
+    regex: bool,
+    /// Find files and directories in trash whose contents match REGEX.
+    /// Works with --restore and --clean.
+    #[clap(long, value_name = "REGEX", verbatim_doc_comment)]
+    grep: Option<String>,
+    /// Print the list of files and directories in trash, or the audit log, as JSON.
+    #[clap(long)]
+    json: bool,
+    /// Restore files or directories to DEST instead of where they came from.
+    /// If DEST is a directory, restore them into it.
+    #[clap(
+        long,
+        value_name = "DEST",
+        requires = "restoring",
+        verbatim_doc_comment
+    )]
+    to: Option<String>,
+    /// Show files or directories in trash without restoring them.
+    /// They can be given by their paths in trash, their original paths or their names.
+    #[clap(long, verbatim_doc_comment)]
+    show: bool,
+    /// Show every trashed version of the given paths with their time and size.
+    #[clap(long)]
+    versions: bool,
+    /// Restore the Nth trashed version(see --versions) instead of the latest one.
+    #[clap(long, value_name = "N", requires = "restore", conflicts_with = "at")]
+    nth: Option<usize>,
+    /// Restore the version which was trashed last at TIME.
+    #[clap(long, value_name = "TIME", requires = "restore")]
+    at: Option<String>,
+    /// Remove directories and their contents recursively.
+    #[clap(short = 'R', long)]
+    recursive: bool,
+    /// Remove empty directories.
+    #[clap(long)]
+    dir: bool,
+    /// Prompt once before removing more than three files or any directory.
+    #[clap(short = 'I')]
+    interactive_once: bool,
     /// Do not prompt before every actions.
     #[clap(short, long)]
     noninteractive: bool,
     /// Do not explain what is being done.
     #[clap(short, long)]
     quiet: bool,
+    /// Remove files with uncommitted changes in git even if noninteractive.
+    #[clap(long)]
+    force: bool,
+    /// Use DIR as the trash root instead of $WRM_TRASH_DIR or ~/.config/wrm.
+    #[clap(long, value_name = "DIR")]
+    trash_dir: Option<String>,
+}
+
+/// Command line options.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    noninteractive: bool,
+    quiet: bool,
+    recursive: bool,
+    dir: bool,
+    interactive_once: bool,
+    note: Option<String>,
+    force: bool,
 }
 
-pub fn argparse(wrm_config_dir: &String) -> Result<()> {
+impl Options {
+    /// Constructs new Options.
+    pub fn new(noninteractive: bool, quiet: bool) -> Self {
+        Options {
+            noninteractive,
+            quiet,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the value of the field "noninteractive".
+    pub fn noninteractive(&self) -> bool {
+        self.noninteractive
+    }
+
+    /// Returns the value of the field "quiet".
+    pub fn quiet(&self) -> bool {
+        self.quiet
+    }
+
+    /// Returns the value of the field "recursive".
+    pub fn recursive(&self) -> bool {
+        self.recursive
+    }
+
+    /// Returns the value of the field "dir".
+    pub fn dir(&self) -> bool {
+        self.dir
+    }
+
+    /// Returns the value of the field "interactive_once".
+    pub fn interactive_once(&self) -> bool {
+        self.interactive_once
+    }
+
+    /// Returns the value of the field "force".
+    pub fn force(&self) -> bool {
+        self.force
+    }
+
+    /// Returns the value of the field "note".
+    pub fn note(&self) -> Option<&String> {
+        self.note.as_ref()
+    }
+
+    pub fn set_recursive(&mut self, value: bool) -> &mut Self {
+        self.recursive = value;
+        self
+    }
+
+    pub fn set_dir(&mut self, value: bool) -> &mut Self {
+        self.dir = value;
+        self
+    }
+
+    pub fn set_interactive_once(&mut self, value: bool) -> &mut Self {
+        self.interactive_once = value;
+        self
+    }
+
+    pub fn set_note(&mut self, value: Option<String>) -> &mut Self {
+        self.note = value;
+        self
+    }
+
+    pub fn set_force(&mut self, value: bool) -> &mut Self {
+        self.force = value;
+        self
+    }
+}
+
+// Exit with the usage error of clap(2) for a value which clap itself can't check.
+fn invalid<T: Display>(message: T) -> ! {
+    Args::command()
+        .error(ErrorKind::InvalidValue, message)
+        .exit()
+}
+
+fn time(value: &str) -> DateTime<Local> {
+    parse_time(value).unwrap_or_else(|e| invalid(e))
+}
+
+// Several files can be restored only into a directory, like mv(1).
+fn check_to(to: Option<&String>, count: usize) {
+    if let Some(to) = to {
+        if count > 1 && !Path::new(to).is_dir() {
+            invalid(NotADirectory { path: to.clone() })
+        }
+    }
+}
+
+pub fn argparse() -> Result<Report> {
     let args = Args::parse();
-    if args.clean {
-        clean(wrm_config_dir, args.noninteractive, args.quiet)?;
+    // Values are checked before anything is done.
+    let at = args.at.as_deref().map(time);
+    let since = args.since.as_deref().map(time);
+    let until = args.until.as_deref().map(time);
+    let restore_since = args.restore_since.as_deref().map(time);
+    let restore_between = args
+        .restore_between
+        .as_ref()
+        .map(|window| (time(&window[0]), time(&window[1])));
+    let compress_before = args.compress.as_deref().map(time);
+    let finder = if args.find.is_some() || args.grep.is_some() {
+        Some(
+            Finder::new(args.find.as_ref(), args.regex, args.grep.as_ref())
+                .unwrap_or_else(|e| invalid(e)),
+        )
+    } else {
+        None
+    };
+    let wrm_path = match &args.trash_dir {
+        Some(dir) => WrmPath::new(dir),
+        None => WrmPath::from_env(),
+    }
+    .expanded()?;
+    // Create the trash root, trash/ and list.json.
+    Trash::new(&wrm_path)?;
+    let wrm_path = &wrm_path;
+    let mut options = Options::new(args.noninteractive, args.quiet);
+    options
+        .set_recursive(args.recursive)
+        .set_dir(args.dir)
+        .set_interactive_once(args.interactive_once)
+        .set_note(args.note)
+        .set_force(args.force);
+    let version = match (args.nth, at) {
+        (Some(0), _) => invalid("Versions are counted from 1"),
+        (Some(n), _) => Version::Number(n),
+        (None, Some(time)) => Version::At(time),
+        (None, None) => Version::Latest,
+    };
+    // Paths in trash selected by --search, --find or --grep
+    let selected = match (&args.search, &finder) {
+        (Some(text), _) => Some(search(wrm_path, text)?),
+        (None, Some(finder)) if args.clean || args.restore => Some(found(wrm_path, finder)?),
+        _ => None,
+    };
+    // Paths in trash selected by --restore-from, --restore-since or --restore-between
+    let restorable = if args.restore_from.is_some()
+        || args.restore_since.is_some()
+        || args.restore_between.is_some()
+    {
+        let (since, until) = match (restore_since, restore_between) {
+            (_, Some((since, until))) => (Some(since), Some(until)),
+            (since, None) => (since, None),
+        };
+        Some(restorable(
+            wrm_path,
+            args.restore_from.as_ref(),
+            since.as_ref(),
+            until.as_ref(),
+        )?)
+    } else {
+        None
+    };
+    // Ctrl-C is caught only while files are changed, so that it stops read-only actions at once.
+    let report = if let Some(path) = restorable {
+        check_to(args.to.as_ref(), path.len());
+        catch();
+        restore(path, wrm_path, args.to.as_ref(), &Version::Latest, &options)?
+    } else if args.clean {
+        catch();
+        if let Some(path) = selected {
+            purge(path, wrm_path, &options)?
+        } else {
+            clean(wrm_path, &options)?
+        }
+    } else if let Some(time) = &compress_before {
+        catch();
+        compress(wrm_path, time, &options)?
+    } else if args.log {
+        log(
+            args.file.unwrap_or_default(),
+            wrm_path,
+            since.as_ref(),
+            until.as_ref(),
+            args.json,
+        )?;
+        Report::new()
+    } else if args.stats {
+        stats(wrm_path, args.json)?;
+        Report::new()
+    } else if args.list && args.all_users {
+        list_all_users(args.search.as_ref(), args.json)?
     } else if args.list {
-        list(wrm_config_dir)?;
+        list(wrm_path, args.search.as_ref(), args.json)?;
+        Report::new()
+    } else if let (true, Some(path)) = (args.restore, selected) {
+        check_to(args.to.as_ref(), path.len());
+        catch();
+        restore(path, wrm_path, args.to.as_ref(), &version, &options)?
+    } else if let Some(finder) = &finder {
+        find(wrm_path, finder, args.json)?;
+        Report::new()
     } else if let Some(path) = args.file {
         if args.delete {
-            delete(path, wrm_config_dir, args.noninteractive, args.quiet)?
+            catch();
+            delete(path, wrm_path, &options)?
         } else if args.restore {
-            restore(path, wrm_config_dir, args.noninteractive, args.quiet)?
+            check_to(args.to.as_ref(), path.len());
+            catch();
+            restore(path, wrm_path, args.to.as_ref(), &version, &options)?
+        } else if args.show {
+            show(path, wrm_path)?
+        } else if args.versions {
+            versions(path, wrm_path)?;
+            Report::new()
+        } else if args.pin || args.unpin {
+            catch();
+            pin(path, wrm_path, args.pin, &options)?
         } else {
-            remove(path, wrm_config_dir, args.noninteractive, args.quiet)?
+            catch();
+            remove(path, wrm_path, &options)?
         }
     } else {
-        let mut cmd = Command::new("wrm");
-        let e = cmd.error(ErrorKind::DisplayHelp, "Incorrect arguments");
-        Err(e).map_err(|e| e.into()).map_err(WrmError)?
-    }
-    Ok(())
+        Args::command()
+            .error(ErrorKind::MissingRequiredArgument, "Incorrect arguments")
+            .exit()
+    };
+    Ok(report)
 }
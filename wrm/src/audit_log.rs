@@ -0,0 +1,211 @@
+use crate::{utils::current_user, Error::WrmError, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+    env, fmt,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+/// Destructive operations recorded in the audit log.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    Trash,
+    Delete,
+    Restore,
+    Purge,
+    Clean,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Trash => write!(f, "trash"),
+            Self::Delete => write!(f, "delete"),
+            Self::Restore => write!(f, "restore"),
+            Self::Purge => write!(f, "purge"),
+            Self::Clean => write!(f, "clean"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Succeeded,
+    Failed,
+    Canceled,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Succeeded => write!(f, "succeeded"),
+            Self::Failed => write!(f, "failed"),
+            Self::Canceled => write!(f, "canceled"),
+        }
+    }
+}
+
+/// A line of the audit log.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Record {
+    timestamp: DateTime<Local>,
+    user: String,
+    cwd: String,
+    path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    path_trash: Option<String>,
+    size: u64,
+    action: Operation,
+    outcome: Outcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Record {
+    pub fn new<P: AsRef<Path>>(action: Operation, path: P, size: u64) -> Self {
+        Record {
+            timestamp: Local::now(),
+            user: current_user(),
+            cwd: env::current_dir()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default(),
+            path: path.as_ref().display().to_string(),
+            path_trash: None,
+            size,
+            action,
+            outcome: Outcome::Succeeded,
+            error: None,
+        }
+    }
+
+    pub fn timestamp(&self) -> &DateTime<Local> {
+        &self.timestamp
+    }
+
+    pub fn path(&self) -> &String {
+        &self.path
+    }
+
+    pub fn action(&self) -> Operation {
+        self.action
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        self.outcome
+    }
+
+    pub fn set_path_trash<P: AsRef<Path>>(&mut self, path_trash: P) -> &mut Self {
+        self.path_trash = Some(path_trash.as_ref().display().to_string());
+        self
+    }
+
+    pub fn set_outcome(&mut self, outcome: Outcome) -> &mut Self {
+        self.outcome = outcome;
+        self
+    }
+
+    pub fn set_error<D: fmt::Display>(&mut self, error: D) -> &mut Self {
+        self.outcome = Outcome::Failed;
+        self.error = Some(error.to_string());
+        self
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} '{}' ({} bytes)",
+            self.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            self.user,
+            self.action,
+            self.outcome,
+            self.path,
+            self.size
+        )?;
+        if let Some(error) = &self.error {
+            write!(f, ": {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// An append-only log of destructive operations, written as JSON lines.
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        AuditLog {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Appends a record to the log.
+    pub fn append(&self, record: &Record) -> Result<()> {
+        let mut f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| e.into())
+            .map_err(WrmError)?;
+        let mut line = serde_json::to_string(record)
+            .map_err(|e| e.into())
+            .map_err(WrmError)?;
+        line.push('\n');
+        // Write the line at once so that concurrent wrm processes don't interleave records.
+        f.write_all(line.as_bytes())
+            .map_err(|e| e.into())
+            .map_err(WrmError)?;
+        Ok(())
+    }
+
+    /// Reads all records from the log.
+    /// Returns no records if the log doesn't exist.
+    pub fn read(&self) -> Result<Vec<Record>> {
+        if !self.path.exists() {
+            return Ok(vec![]);
+        }
+        let f = File::open(&self.path)
+            .map_err(|e| e.into())
+            .map_err(WrmError)?;
+        let mut records = vec![];
+        for line in BufReader::new(f).lines() {
+            let line = line.map_err(|e| e.into()).map_err(WrmError)?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            records.push(
+                serde_json::from_str(&line)
+                    .map_err(|e| e.into())
+                    .map_err(WrmError)?,
+            );
+        }
+        Ok(records)
+    }
+
+    /// Reads the records in a time range whose paths are any of `paths` or under them.
+    /// No paths means all of them.
+    pub fn query(
+        &self,
+        paths: &[PathBuf],
+        since: Option<&DateTime<Local>>,
+        until: Option<&DateTime<Local>>,
+    ) -> Result<Vec<Record>> {
+        Ok(self
+            .read()?
+            .into_iter()
+            .filter(|r| {
+                since.map(|t| r.timestamp() >= t).unwrap_or(true)
+                    && until.map(|t| r.timestamp() <= t).unwrap_or(true)
+                    && (paths.is_empty()
+                        || paths.iter().any(|p| Path::new(r.path()).starts_with(p)))
+            })
+            .collect())
+    }
+}
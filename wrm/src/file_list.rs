@@ -1,29 +1,154 @@
-use crate::{Error::WrmError, Result};
-use filey::{Error::GetFileNameError, Filey};
+use crate::{
+    utils::size,
+    wrm_path::WrmPath,
+    Error::{UnsupportedVersion, WrmError},
+    Result,
+};
+use chrono::{DateTime, Local};
+use filey::{Error::GetFileNameError, FileTypes, Filey};
 use serde::{Deserialize, Serialize};
-use std::{fs::File, path::Path};
+use serde_json::Value;
+use std::{
+    fs::{copy, rename, symlink_metadata, File},
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+/// The version of the list.json format which this wrm reads and writes.
+/// Older lists are migrated to it, and newer ones are refused.
+pub const LIST_VERSION: u64 = 2;
+
+/// Which of the trashed versions of a path to pick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Version {
+    /// The most recently trashed one.
+    Latest,
+    /// The nth trashed one, counting from 1.
+    Number(usize),
+    /// The most recently trashed one at the time.
+    At(DateTime<Local>),
+}
+
+/// Ownership and permissions of a file before it was moved to trash.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Owner {
+    uid: u32,
+    gid: u32,
+    mode: u32,
+}
+
+impl Owner {
+    /// Returns the owner of the file, not following symbolic links.
+    pub fn of<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let metadata = symlink_metadata(path).ok()?;
+        Some(Owner {
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            mode: metadata.mode(),
+        })
+    }
+
+    pub fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    pub fn gid(&self) -> u32 {
+        self.gid
+    }
+
+    /// Returns the permission bits including setuid, setgid and sticky bits.
+    pub fn mode(&self) -> u32 {
+        self.mode & 0o7777
+    }
+}
+
+/// How a file or a directory in trash is packed into a compressed archive.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Archived {
+    file_type: FileTypes,
+    size: u64,
+    archived_at: DateTime<Local>,
+}
+
+impl Archived {
+    pub fn new(file_type: FileTypes, size: u64) -> Self {
+        Archived {
+            file_type,
+            size,
+            archived_at: Local::now(),
+        }
+    }
+
+    /// Returns the file type before it was packed.
+    pub fn file_type(&self) -> FileTypes {
+        self.file_type
+    }
+
+    /// Returns the size in bytes before it was packed.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn archived_at(&self) -> &DateTime<Local> {
+        &self.archived_at
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct FileInfo {
     path: String,
     path_trash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pinned: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    deleted_at: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    owner: Option<Owner>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    archived: Option<Archived>,
 }
 
 impl FileInfo {
-    pub fn new<P: AsRef<Path>>(path: P, wrm_config_dir: &String) -> Result<Self> {
+    /// Constructs a new FileInfo.
+    /// If a file with the same name is already in trash, a number is appended to the name
+    /// so that every trashed version is kept.
+    pub fn new<P: AsRef<Path>>(path: P, wrm_path: &WrmPath) -> Result<Self> {
         let path = path.as_ref().display().to_string();
-        let path_trash = format!(
-            "{}/trash/{}",
-            wrm_config_dir,
-            Filey::new(&path)
-                .file_name()
-                .ok_or_else(|| GetFileNameError {
-                    path: path.to_string()
-                })
-                .map_err(|e| e.into())
-                .map_err(WrmError)?
-        );
-        let fileinfo = FileInfo { path, path_trash };
+        let file_name = Filey::new(&path)
+            .file_name()
+            .ok_or_else(|| GetFileNameError {
+                path: path.to_string(),
+            })
+            .map_err(|e| e.into())
+            .map_err(WrmError)?;
+        let mut path_trash = wrm_path.trash().join(&file_name);
+        let mut n = 1;
+        // The name is taken if it or its archive exists, or if it is the archive name of
+        // another entry, which that entry needs when it is compressed.
+        let is_taken = |p: &PathBuf| {
+            let p = p.display().to_string();
+            Filey::new(&p).exists()
+                || Path::new(&archive_path(&p)).exists()
+                || p.strip_suffix(ARCHIVE_SUFFIX)
+                    .map(|unpacked| Filey::new(unpacked).exists())
+                    .unwrap_or(false)
+        };
+        while is_taken(&path_trash) {
+            path_trash = wrm_path.trash().join(format!("{}.{}", file_name, n));
+            n += 1;
+        }
+        let owner = Owner::of(&path);
+        let fileinfo = FileInfo {
+            path,
+            path_trash: path_trash.display().to_string(),
+            note: None,
+            pinned: false,
+            deleted_at: Some(Local::now()),
+            owner,
+            archived: None,
+        };
         Ok(fileinfo)
     }
 
@@ -34,36 +159,298 @@ impl FileInfo {
     pub fn path_trash(&self) -> &String {
         &self.path_trash
     }
+
+    /// Returns how the file is packed if it is archived.
+    pub fn archived(&self) -> Option<&Archived> {
+        self.archived.as_ref()
+    }
+
+    pub fn set_archived(&mut self, archived: Option<Archived>) -> &mut Self {
+        self.archived = archived;
+        self
+    }
+
+    /// Returns the path to the archive which the file is packed into.
+    pub fn archive_path(&self) -> String {
+        archive_path(&self.path_trash)
+    }
+
+    /// Returns the size in bytes. An archived file has the size recorded when it was packed,
+    /// not the size of its archive.
+    pub fn size(&self) -> u64 {
+        match &self.archived {
+            Some(archived) => archived.size(),
+            None => size(&self.path_trash),
+        }
+    }
+
+    /// Returns where the file actually is in trash: the archive if it is archived,
+    /// otherwise its path in trash.
+    pub fn stored_path(&self) -> String {
+        match self.archived {
+            Some(_) => self.archive_path(),
+            None => self.path_trash.clone(),
+        }
+    }
+
+    /// Returns when the file was moved to trash.
+    /// Files trashed by old versions of wrm don't have it.
+    pub fn deleted_at(&self) -> Option<&DateTime<Local>> {
+        self.deleted_at.as_ref()
+    }
+
+    /// Returns who owned the file and its mode before it was moved to trash.
+    /// Files trashed by old versions of wrm don't have it.
+    pub fn owner(&self) -> Option<&Owner> {
+        self.owner.as_ref()
+    }
+
+    pub fn note(&self) -> Option<&String> {
+        self.note.as_ref()
+    }
+
+    pub fn set_note(&mut self, note: Option<String>) -> &mut Self {
+        self.note = note;
+        self
+    }
+
+    pub fn pinned(&self) -> bool {
+        self.pinned
+    }
+
+    pub fn set_pinned(&mut self, value: bool) -> &mut Self {
+        self.pinned = value;
+        self
+    }
+
+    /// Returns true if the note contains the given text(case insensitive).
+    pub fn note_contains(&self, text: &str) -> bool {
+        self.note
+            .as_ref()
+            .map(|n| n.to_lowercase().contains(&text.to_lowercase()))
+            .unwrap_or(false)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct FileList {
+    version: u64,
     files: Vec<FileInfo>,
 }
 
+impl Default for FileList {
+    fn default() -> Self {
+        FileList::new()
+    }
+}
+
 impl FileList {
     pub fn new() -> Self {
-        FileList { files: vec![] }
+        FileList::with_files(vec![])
+    }
+
+    fn with_files(files: Vec<FileInfo>) -> Self {
+        FileList {
+            version: LIST_VERSION,
+            files,
+        }
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
     }
 
     pub fn files(&self) -> &Vec<FileInfo> {
         &self.files
     }
 
+    /// Returns a list of files whose note contains the given text.
+    pub fn search(&self, text: &str) -> FileList {
+        let files = self
+            .files
+            .iter()
+            .filter(|i| i.note_contains(text))
+            .cloned()
+            .collect();
+        FileList::with_files(files)
+    }
+
+    /// Returns a list of pinned files.
+    pub fn pinned(&self) -> FileList {
+        let files = self.files.iter().filter(|i| i.pinned()).cloned().collect();
+        FileList::with_files(files)
+    }
+
+    /// Returns a list of files which are not pinned.
+    pub fn unpinned(&self) -> FileList {
+        let files = self.files.iter().filter(|i| !i.pinned()).cloned().collect();
+        FileList::with_files(files)
+    }
+
+    /// Finds a file by its path in trash or, failing that, by its original path.
+    /// The most recently added file wins if several files have the same original path.
+    pub fn find(&self, path: &str) -> Option<&FileInfo> {
+        let index = self.position(path)?;
+        self.files.get(index)
+    }
+
+    pub fn find_mut(&mut self, path: &str) -> Option<&mut FileInfo> {
+        let index = self.position(path)?;
+        self.files.get_mut(index)
+    }
+
+    /// Finds a file by its name in trash or its original name.
+    /// The most recently added file wins if several files have the same name.
+    pub fn find_by_name(&self, name: &str) -> Option<&FileInfo> {
+        let file_name = |p: &String| Filey::new(p).file_name();
+        self.files
+            .iter()
+            .rev()
+            .find(|i| file_name(i.path_trash()).as_deref() == Some(name))
+            .or_else(|| {
+                self.files
+                    .iter()
+                    .rev()
+                    .find(|i| file_name(i.path()).as_deref() == Some(name))
+            })
+    }
+
+    /// Returns a list of files whose original paths are the directory or under it.
+    pub fn under<P: AsRef<Path>>(&self, dir: P) -> FileList {
+        let files = self
+            .files
+            .iter()
+            .filter(|i| Path::new(i.path()).starts_with(&dir))
+            .cloned()
+            .collect();
+        FileList::with_files(files)
+    }
+
+    /// Returns a list of files trashed in the time window.
+    /// Files trashed by old versions of wrm, whose time isn't known, are never in it.
+    pub fn deleted_between(
+        &self,
+        since: Option<&DateTime<Local>>,
+        until: Option<&DateTime<Local>>,
+    ) -> FileList {
+        let files = self
+            .files
+            .iter()
+            .filter(|i| match i.deleted_at() {
+                Some(t) => {
+                    since.map(|s| t >= s).unwrap_or(true) && until.map(|u| t <= u).unwrap_or(true)
+                }
+                None => false,
+            })
+            .cloned()
+            .collect();
+        FileList::with_files(files)
+    }
+
+    /// Returns a list of the latest trashed version of each original path.
+    pub fn latest(&self) -> FileList {
+        let files = self
+            .files
+            .iter()
+            .enumerate()
+            .filter(|(n, i)| !self.files[n + 1..].iter().any(|j| j.path() == i.path()))
+            .map(|(_, i)| i.clone())
+            .collect();
+        FileList::with_files(files)
+    }
+
+    /// Returns all trashed versions of the original path of the given file, oldest first.
+    pub fn versions(&self, path: &str) -> Vec<&FileInfo> {
+        let original = self
+            .files
+            .iter()
+            .find(|i| i.path_trash() == path)
+            .map(|i| i.path().as_str())
+            .unwrap_or(path);
+        self.files.iter().filter(|i| i.path() == original).collect()
+    }
+
+    /// Finds a file like find(), picking the given version of it.
+    pub fn find_version(&self, path: &str, version: &Version) -> Option<&FileInfo> {
+        match version {
+            Version::Latest => self.find(path),
+            Version::Number(n) => self.versions(path).get(n.checked_sub(1)?).copied(),
+            Version::At(time) => self
+                .versions(path)
+                .into_iter()
+                .rev()
+                .find(|i| i.deleted_at().map(|t| t <= time).unwrap_or(true)),
+        }
+    }
+
+    fn position(&self, path: &str) -> Option<usize> {
+        self.files
+            .iter()
+            .position(|i| i.path_trash() == path)
+            .or_else(|| self.files.iter().rposition(|i| i.path() == path))
+    }
+
+    /// Reads a list. A list of an older version is migrated in memory.
     pub fn read<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let f = File::open(path).map_err(|e| e.into()).map_err(WrmError)?;
-        let files = serde_json::from_reader(f)
+        Ok(FileList::read_versioned(path)?.0)
+    }
+
+    // Returns the list and the version it was written in.
+    fn read_versioned<P: AsRef<Path>>(path: P) -> Result<(Self, u64)> {
+        let f = File::open(&path).map_err(|e| e.into()).map_err(WrmError)?;
+        let mut value: Value = serde_json::from_reader(f)
+            .map_err(|e| e.into())
+            .map_err(WrmError)?;
+        // Lists written before versioning have no version.
+        let version = match value.get("version") {
+            None => 0,
+            Some(v) => v.as_u64().ok_or_else(|| UnsupportedVersion {
+                path: path.as_ref().display().to_string(),
+                version: v.to_string(),
+            })?,
+        };
+        if version > LIST_VERSION {
+            return Err(UnsupportedVersion {
+                path: path.as_ref().display().to_string(),
+                version: version.to_string(),
+            });
+        }
+        for from in version..LIST_VERSION {
+            value = migrate(value, from);
+        }
+        let file_list = serde_json::from_value(value)
+            .map_err(|e| e.into())
+            .map_err(WrmError)?;
+        Ok((file_list, version))
+    }
+
+    /// Rewrites a list of an older version in the current version.
+    /// The original is kept as `list.json.v<VERSION>.bak`, and its version is returned.
+    pub fn migrate<P: AsRef<Path>>(path: P) -> Result<Option<u64>> {
+        let (file_list, version) = FileList::read_versioned(&path)?;
+        if version == LIST_VERSION {
+            return Ok(None);
+        }
+        copy(&path, backup_path(path.as_ref(), version))
             .map_err(|e| e.into())
             .map_err(WrmError)?;
-        Ok(files)
+        file_list.write(&path)?;
+        Ok(Some(version))
     }
 
+    /// Writes the list to a temporary file next to `path` and renames it to `path`,
+    /// so that `path` is never left half written.
     pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        let f = File::create(path).map_err(|e| e.into()).map_err(WrmError)?;
-        serde_json::to_writer_pretty(f, &self)
+        let mut temp = path.as_ref().as_os_str().to_owned();
+        temp.push(".tmp");
+        let f = File::create(&temp)
+            .map_err(|e| e.into())
+            .map_err(WrmError)?;
+        serde_json::to_writer_pretty(&f, &self)
+            .and_then(|_| f.sync_all().map_err(serde_json::Error::io))
             .map_err(|e| e.into())
             .map_err(WrmError)?;
-        Ok(())
+        rename(&temp, path).map_err(|e| e.into()).map_err(WrmError)
     }
 
     pub fn add(&mut self, fileinfo: &FileInfo) -> &mut Self {
@@ -72,7 +459,36 @@ impl FileList {
     }
 
     pub fn remove(&mut self, fileinfo: &FileInfo) -> &mut Self {
-        self.files.retain(|x| x != fileinfo);
+        self.files
+            .retain(|x| x.path_trash() != fileinfo.path_trash());
         self
     }
 }
+
+// Migrate a list from the given version to the next one.
+// Version 1 added the version, and version 2 archived entries. Only a wrm which knows versions
+// refuses a newer list; an older one ignores the version and drops archived entries as missing.
+// Newer fields of FileInfo have defaults, so only the version changes.
+fn migrate(mut value: Value, from: u64) -> Value {
+    if let Value::Object(map) = &mut value {
+        map.insert("version".to_string(), Value::from(from + 1));
+    }
+    value
+}
+
+const ARCHIVE_SUFFIX: &str = ".tar.gz";
+
+fn archive_path(path_trash: &str) -> String {
+    format!("{}{}", path_trash, ARCHIVE_SUFFIX)
+}
+
+// A backup never overwrites an older backup.
+fn backup_path(path: &Path, version: u64) -> PathBuf {
+    let mut backup = PathBuf::from(format!("{}.v{}.bak", path.display(), version));
+    let mut n = 1;
+    while backup.exists() {
+        backup = PathBuf::from(format!("{}.v{}.bak.{}", path.display(), version, n));
+        n += 1;
+    }
+    backup
+}
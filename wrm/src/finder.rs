@@ -0,0 +1,198 @@
+use crate::{file_list::FileInfo, Error::WrmError, Result};
+use filey::Filey;
+use flate2::read::GzDecoder;
+use glob::Pattern;
+use regex::Regex;
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+};
+use tar::Archive;
+use walkdir::WalkDir;
+
+enum Matcher {
+    Glob(Pattern),
+    Regex(Regex),
+}
+
+/// A line matching --grep in a file in trash.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Line {
+    path: PathBuf,
+    number: usize,
+    text: String,
+}
+
+impl Line {
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn number(&self) -> usize {
+        self.number
+    }
+
+    pub fn text(&self) -> &String {
+        &self.text
+    }
+}
+
+/// A file or directory in trash found by a Finder, with the lines matching --grep in it.
+#[derive(Serialize, Debug)]
+pub struct Found<'a> {
+    #[serde(flatten)]
+    file_info: &'a FileInfo,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    lines: Vec<Line>,
+}
+
+impl Found<'_> {
+    pub fn file_info(&self) -> &FileInfo {
+        self.file_info
+    }
+
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+}
+
+/// Finds files and directories in trash by their original paths and their contents.
+pub struct Finder {
+    name: Option<Matcher>,
+    content: Option<Regex>,
+}
+
+impl Finder {
+    /// Constructs a new Finder.
+    /// `pattern` is a glob unless `regex` is true. `grep` is a regex to search contents.
+    pub fn new(pattern: Option<&String>, regex: bool, grep: Option<&String>) -> Result<Self> {
+        let name = match pattern {
+            Some(p) if regex => Some(Matcher::Regex(
+                Regex::new(p).map_err(|e| e.into()).map_err(WrmError)?,
+            )),
+            Some(p) => Some(Matcher::Glob(
+                Pattern::new(p).map_err(|e| e.into()).map_err(WrmError)?,
+            )),
+            None => None,
+        };
+        let content = grep
+            .map(|g| Regex::new(g).map_err(|e| e.into()).map_err(WrmError))
+            .transpose()?;
+        Ok(Finder { name, content })
+    }
+
+    /// Returns true if the original path matches.
+    /// A glob without '/' is matched against the file name, otherwise against the whole path.
+    pub fn matches_path(&self, file_info: &FileInfo) -> bool {
+        match &self.name {
+            None => true,
+            Some(Matcher::Regex(r)) => r.is_match(file_info.path()),
+            Some(Matcher::Glob(g)) => {
+                if g.as_str().contains('/') {
+                    g.matches(file_info.path())
+                } else {
+                    Filey::new(file_info.path())
+                        .file_name()
+                        .map(|n| g.matches(&n))
+                        .unwrap_or(false)
+                }
+            }
+        }
+    }
+
+    /// Returns lines matching --grep in the file in trash, recursing into directories.
+    /// Archived files are read from their archives. Binary files are skipped.
+    pub fn grep(&self, file_info: &FileInfo) -> Vec<Line> {
+        let regex = match &self.content {
+            Some(r) => r,
+            None => return vec![],
+        };
+        if file_info.archived().is_some() {
+            return grep_archive(regex, file_info).unwrap_or_default();
+        }
+        let mut lines = vec![];
+        for entry in WalkDir::new(file_info.path_trash())
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+        {
+            if is_binary(entry.path()) {
+                continue;
+            }
+            let f = match File::open(entry.path()) {
+                Ok(f) => f,
+                Err(_) => continue,
+            };
+            grep_lines(regex, entry.path(), BufReader::new(f), &mut lines);
+        }
+        lines
+    }
+
+    /// Returns the lines matching --grep if the file matches all the given conditions,
+    /// so that contents are read only once. The lines are empty without --grep.
+    pub fn find<'a>(&self, file_info: &'a FileInfo) -> Option<Found<'a>> {
+        if !self.matches_path(file_info) {
+            return None;
+        }
+        let lines = self.grep(file_info);
+        if self.content.is_some() && lines.is_empty() {
+            return None;
+        }
+        Some(Found { file_info, lines })
+    }
+
+    /// Returns true if the file matches all the given conditions.
+    pub fn matches(&self, file_info: &FileInfo) -> bool {
+        self.find(file_info).is_some()
+    }
+}
+
+fn grep_lines<R: BufRead>(regex: &Regex, path: &Path, reader: R, lines: &mut Vec<Line>) {
+    for (n, text) in reader.lines().map_while(|l| l.ok()).enumerate() {
+        if regex.is_match(&text) {
+            lines.push(Line {
+                path: path.to_path_buf(),
+                number: n + 1,
+                text,
+            });
+        }
+    }
+}
+
+// Entries in an archive are named from the file name, so they are shown as if it were unpacked.
+fn grep_archive(regex: &Regex, file_info: &FileInfo) -> std::io::Result<Vec<Line>> {
+    let dir = Path::new(file_info.path_trash())
+        .parent()
+        .unwrap_or_else(|| Path::new("/"));
+    let f = File::open(file_info.archive_path())?;
+    let mut archive = Archive::new(GzDecoder::new(f));
+    let mut lines = vec![];
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = dir.join(entry.path()?);
+        let mut buffer = vec![];
+        entry.read_to_end(&mut buffer)?;
+        if buffer[..buffer.len().min(8192)].contains(&0) {
+            continue;
+        }
+        grep_lines(regex, &path, buffer.as_slice(), &mut lines);
+    }
+    // Like WalkDir sorted by file name.
+    lines.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(lines)
+}
+
+// A file is binary if its head contains NUL like grep(1) does.
+fn is_binary(path: &Path) -> bool {
+    let mut buffer = vec![];
+    match File::open(path) {
+        Ok(f) => f.take(8192).read_to_end(&mut buffer).is_err() || buffer.contains(&0),
+        Err(_) => true,
+    }
+}
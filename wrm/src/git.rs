@@ -0,0 +1,97 @@
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+/// Files with uncommitted changes in a git working tree.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Changes {
+    modified: Vec<PathBuf>,
+    untracked: Vec<PathBuf>,
+}
+
+impl Changes {
+    /// Returns files which are modified, added, deleted or renamed but not committed.
+    pub fn modified(&self) -> &Vec<PathBuf> {
+        &self.modified
+    }
+
+    /// Returns files which are neither tracked nor ignored.
+    pub fn untracked(&self) -> &Vec<PathBuf> {
+        &self.untracked
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.modified.is_empty() && self.untracked.is_empty()
+    }
+}
+
+/// Returns uncommitted changes of a file or files under a directory.
+///
+/// The repository is read locally with git(1). Returns None if the path isn't in a
+/// working tree, or git isn't available.
+pub fn changes<P: AsRef<Path>>(path: P) -> Option<Changes> {
+    let path = path.as_ref();
+    let dir = if path.is_dir() && !path.is_symlink() {
+        path
+    } else {
+        path.parent()?
+    };
+    let root = git(dir, &["rev-parse", "--show-toplevel"])?;
+    let root = PathBuf::from(String::from_utf8_lossy(&root).trim_end());
+    let status = git(
+        dir,
+        &[
+            OsStr::new("status"),
+            OsStr::new("--porcelain"),
+            OsStr::new("-z"),
+            OsStr::new("--untracked-files=all"),
+            OsStr::new("--"),
+            path.as_os_str(),
+        ],
+    )?;
+    Some(parse_status(&root, &status))
+}
+
+/// Parses the output of `git status --porcelain -z` run in the working tree at `root`.
+pub fn parse_status(root: &Path, status: &[u8]) -> Changes {
+    let mut changes = Changes::default();
+    // Entries are like "XY path", separated by NUL.
+    let mut entries = status.split(|b| *b == 0);
+    while let Some(entry) = entries.next() {
+        if entry.len() < 4 {
+            continue;
+        }
+        let (code, file) = entry.split_at(3);
+        let file = root.join(String::from_utf8_lossy(file).as_ref());
+        match &code[..2] {
+            b"??" => changes.untracked.push(file),
+            b"!!" => (),
+            code => {
+                // Renames and copies are followed by their sources.
+                if code.contains(&b'R') || code.contains(&b'C') {
+                    entries.next();
+                }
+                changes.modified.push(file);
+            }
+        }
+    }
+    changes
+}
+
+fn git<S: AsRef<OsStr>>(dir: &Path, args: &[S]) -> Option<Vec<u8>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if output.status.success() {
+        Some(output.stdout)
+    } else {
+        None
+    }
+}
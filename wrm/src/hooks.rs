@@ -0,0 +1,197 @@
+use crate::{
+    file_list::FileInfo,
+    utils::current_uid,
+    Error::{HookFailed, UnsafeHooks, WrmError},
+    Result,
+};
+use filey::FileTypes;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    fs::{metadata, File},
+    io::Write,
+    os::unix::fs::MetadataExt,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+/// Actions which hooks can be attached to.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Remove,
+    Delete,
+    Restore,
+    Clean,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Remove => write!(f, "remove"),
+            Self::Delete => write!(f, "delete"),
+            Self::Restore => write!(f, "restore"),
+            Self::Clean => write!(f, "clean"),
+        }
+    }
+}
+
+/// Whether a hook runs before or after an action.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    Pre,
+    Post,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Pre => write!(f, "pre"),
+            Self::Post => write!(f, "post"),
+        }
+    }
+}
+
+/// What a hook is told about an action.
+/// It is passed to the hook as JSON on stdin.
+#[derive(Serialize, Debug, Clone)]
+pub struct Event {
+    action: Action,
+    stage: Stage,
+    paths: Vec<String>,
+    file_type: Option<FileTypes>,
+    files: Vec<FileInfo>,
+}
+
+impl Event {
+    pub fn new(action: Action, paths: Vec<String>) -> Self {
+        Event {
+            action,
+            stage: Stage::Pre,
+            paths,
+            file_type: None,
+            files: vec![],
+        }
+    }
+
+    pub fn set_file_type(&mut self, file_type: FileTypes) -> &mut Self {
+        self.file_type = Some(file_type);
+        self
+    }
+
+    pub fn set_files(&mut self, files: Vec<FileInfo>) -> &mut Self {
+        self.files = files;
+        self
+    }
+}
+
+/// Shell commands run before and after actions, read from hooks.json.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Hooks {
+    #[serde(default)]
+    pre_remove: Option<String>,
+    #[serde(default)]
+    post_remove: Option<String>,
+    #[serde(default)]
+    pre_delete: Option<String>,
+    #[serde(default)]
+    post_delete: Option<String>,
+    #[serde(default)]
+    pre_restore: Option<String>,
+    #[serde(default)]
+    post_restore: Option<String>,
+    #[serde(default)]
+    pre_clean: Option<String>,
+    #[serde(default)]
+    post_clean: Option<String>,
+}
+
+impl Hooks {
+    /// Reads hooks from the given path.
+    /// Returns no hooks if the file doesn't exist. The hooks are run as shell commands, so a file
+    /// which isn't owned by the effective user, or which group or others can write, is refused.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self> {
+        if !path.as_ref().exists() {
+            return Ok(Hooks::default());
+        }
+        let m = metadata(&path).map_err(|e| e.into()).map_err(WrmError)?;
+        if m.uid() != current_uid() || m.mode() & 0o022 != 0 {
+            return Err(UnsafeHooks {
+                path: path.as_ref().display().to_string(),
+            });
+        }
+        let f = File::open(path).map_err(|e| e.into()).map_err(WrmError)?;
+        let hooks = serde_json::from_reader(f)
+            .map_err(|e| e.into())
+            .map_err(WrmError)?;
+        Ok(hooks)
+    }
+
+    fn command(&self, stage: Stage, action: Action) -> Option<&String> {
+        match (stage, action) {
+            (Stage::Pre, Action::Remove) => self.pre_remove.as_ref(),
+            (Stage::Post, Action::Remove) => self.post_remove.as_ref(),
+            (Stage::Pre, Action::Delete) => self.pre_delete.as_ref(),
+            (Stage::Post, Action::Delete) => self.post_delete.as_ref(),
+            (Stage::Pre, Action::Restore) => self.pre_restore.as_ref(),
+            (Stage::Post, Action::Restore) => self.post_restore.as_ref(),
+            (Stage::Pre, Action::Clean) => self.pre_clean.as_ref(),
+            (Stage::Post, Action::Clean) => self.post_clean.as_ref(),
+        }
+    }
+
+    /// Runs the pre hook of the event's action.
+    /// An error means the action must not be done.
+    pub fn pre(&self, event: &mut Event) -> Result<()> {
+        event.stage = Stage::Pre;
+        self.run(event)
+    }
+
+    /// Runs the post hook of the event's action.
+    pub fn post(&self, event: &mut Event) -> Result<()> {
+        event.stage = Stage::Post;
+        self.run(event)
+    }
+
+    // Run the hook with `sh -c`.
+    // The event is given as JSON on stdin and as WRM_* environment variables.
+    fn run(&self, event: &Event) -> Result<()> {
+        let command = match self.command(event.stage, event.action) {
+            Some(c) => c,
+            None => return Ok(()),
+        };
+        let hook = format!("{}_{}", event.stage, event.action);
+        let json = serde_json::to_string(event)
+            .map_err(|e| e.into())
+            .map_err(WrmError)?;
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("WRM_HOOK", &hook)
+            .env("WRM_ACTION", event.action.to_string())
+            .env("WRM_STAGE", event.stage.to_string())
+            .env("WRM_PATHS", event.paths.join("\n"))
+            .env(
+                "WRM_FILE_TYPE",
+                event.file_type.map(|t| t.to_string()).unwrap_or_default(),
+            )
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| e.into())
+            .map_err(WrmError)?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // The hook may exit without reading stdin.
+            let _ = stdin.write_all(json.as_bytes());
+        }
+        let status = child.wait().map_err(|e| e.into()).map_err(WrmError)?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(HookFailed {
+                hook,
+                reason: status.to_string(),
+            })
+        }
+    }
+}
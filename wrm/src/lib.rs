@@ -0,0 +1,117 @@
+//! wrm
+//!
+//! A library to move files and directories to trash, restore them and delete them permanently.
+//! It is what the command wrm is built on.
+//!
+//! ```no_run
+//! # fn main() -> wrm::Result<()> {
+//! use wrm::Trash;
+//!
+//! let trash = Trash::new("/tmp/wrm")?;
+//! trash.put("old.txt", None)?;
+//! for file_info in trash.list()?.files() {
+//!     println!("{} ({})", file_info.path_trash(), file_info.path());
+//! }
+//! trash.empty(false)?;
+//! # Ok(())
+//! # }
+//! ```
+pub mod file_list;
+mod trash;
+mod wrm_path;
+
+// Shared with the wrm command, but not part of the library's API.
+#[doc(hidden)]
+pub mod audit_log;
+#[doc(hidden)]
+pub mod finder;
+#[doc(hidden)]
+pub mod git;
+#[doc(hidden)]
+pub mod hooks;
+#[doc(hidden)]
+pub mod stats;
+#[doc(hidden)]
+pub mod utils;
+
+pub use crate::{
+    file_list::{FileInfo, FileList},
+    trash::Trash,
+    wrm_path::{WrmPath, TRASH_DIR_ENV},
+};
+
+#[derive(Debug, thiserror::Error)]
+#[error(transparent)]
+pub enum Error {
+    WrmError(anyhow::Error),
+    #[error("'{}' No such file or directory", path)]
+    NotFoundError {
+        path: String,
+    },
+    #[error("'{}' Is a directory", path)]
+    IsADirectory {
+        path: String,
+    },
+    #[error("'{}' Not a directory", path)]
+    NotADirectory {
+        path: String,
+    },
+    #[error("'{}' already exists", path)]
+    AlreadyExists {
+        path: String,
+    },
+    #[error("'{}' Directory not empty", path)]
+    DirectoryNotEmpty {
+        path: String,
+    },
+    #[error("'{}' Invalid time", time)]
+    InvalidTime {
+        time: String,
+    },
+    #[error("'{}' is pinned", path)]
+    Pinned {
+        path: String,
+    },
+    #[error(
+        "'{}' has list version {}, which is newer than this wrm supports({})",
+        path,
+        version,
+        file_list::LIST_VERSION
+    )]
+    UnsupportedVersion {
+        path: String,
+        version: String,
+    },
+    #[error(
+        "'{}' has uncommitted changes in git(use --force to remove it anyway)",
+        path
+    )]
+    UncommittedChanges {
+        path: String,
+    },
+    #[error(
+        "'{}' is the trash of wrm or contains it(use --clean to empty the trash)",
+        path
+    )]
+    ContainsTrash {
+        path: String,
+    },
+    #[error("'{}' is in the trash root of wrm", path)]
+    InTrash {
+        path: String,
+    },
+    #[error("{} hook failed: {}", hook, reason)]
+    HookFailed {
+        hook: String,
+        reason: String,
+    },
+    #[error(
+        "'{}' isn't owned by the current user or is writable by others, so its hooks aren't run",
+        path
+    )]
+    UnsafeHooks {
+        path: String,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
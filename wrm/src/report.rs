@@ -0,0 +1,125 @@
+use crate::interrupt::interrupted;
+use colored::Colorize;
+use std::fmt::Display;
+
+/// Every item succeeded, or there was nothing to do.
+pub const SUCCESS: i32 = 0;
+/// Some items failed.
+pub const SOME_FAILED: i32 = 1;
+// 2 is what clap exits with when the arguments are incorrect, as values which clap can't check do.
+/// No item succeeded because all of them failed or were canceled.
+pub const ALL_FAILED: i32 = 3;
+/// An error stopped wrm, e.g. the trash couldn't be read, so the outcomes of items are unknown.
+pub const FATAL: i32 = 4;
+/// Interrupted with Ctrl-C, like shells report 128 + SIGINT.
+pub const INTERRUPTED: i32 = 130;
+
+/// Outcomes of the files and directories given to an action.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    succeeded: usize,
+    canceled: usize,
+    // Left alone on purpose, like pinned files on purging. They don't count for the exit status.
+    skipped: usize,
+    failed: Vec<(String, String)>,
+    not_processed: Vec<String>,
+}
+
+impl Report {
+    /// Constructs a new Report.
+    pub fn new() -> Self {
+        Report::default()
+    }
+
+    /// Constructs a Report where all the given items were canceled at once.
+    pub fn canceled(count: usize) -> Self {
+        Report {
+            canceled: count,
+            ..Report::default()
+        }
+    }
+
+    pub fn succeed(&mut self) -> &mut Self {
+        self.succeeded += 1;
+        self
+    }
+
+    pub fn cancel(&mut self) -> &mut Self {
+        self.canceled += 1;
+        self
+    }
+
+    pub fn skip(&mut self) -> &mut Self {
+        self.skipped += 1;
+        self
+    }
+
+    /// Prints the error and records it for the summary.
+    pub fn fail<P: Display, E: Display>(&mut self, path: P, error: E) -> &mut Self {
+        eprintln!("error: {}", error);
+        self.failed.push((path.to_string(), error.to_string()));
+        self
+    }
+
+    /// Records the files and directories which weren't processed because of Ctrl-C.
+    pub fn interrupt<S: ToString>(&mut self, rest: &[S]) -> &mut Self {
+        self.not_processed
+            .extend(rest.iter().map(|i| i.to_string()));
+        self
+    }
+
+    fn total(&self) -> usize {
+        self.succeeded + self.canceled + self.skipped + self.failed.len()
+    }
+
+    /// Returns the exit status of wrm.
+    pub fn exit_code(&self) -> i32 {
+        if interrupted() {
+            INTERRUPTED
+        } else if self.succeeded == 0 && self.total() > self.skipped {
+            ALL_FAILED
+        } else if !self.failed.is_empty() {
+            SOME_FAILED
+        } else {
+            SUCCESS
+        }
+    }
+
+    /// Prints which files and directories failed and why at the end of a run.
+    /// A single failure has been printed already, so it isn't repeated.
+    /// If interrupted, prints how many were processed and which weren't.
+    pub fn summarize(&self) {
+        if interrupted() {
+            eprintln!(
+                "{} {} of {} processed({} succeeded, {} canceled, {} skipped, {} failed), not processed:",
+                "Interrupted".red().bold(),
+                self.total(),
+                self.total() + self.not_processed.len(),
+                self.succeeded,
+                self.canceled,
+                self.skipped,
+                self.failed.len()
+            );
+            for path in &self.not_processed {
+                eprintln!("    {}", path);
+            }
+        }
+        if self.failed.is_empty() || self.total() < 2 {
+            return;
+        }
+        eprintln!(
+            "{} {} of {} failed:",
+            "Summary".red().bold(),
+            self.failed.len(),
+            self.total()
+        );
+        for (path, reason) in &self.failed {
+            // Most errors already tell the path.
+            if reason.contains(path.as_str()) {
+                eprintln!("    {}", reason);
+            } else {
+                eprintln!("    '{}' {}", path, reason);
+            }
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use crate::file_list::{FileInfo, FileList};
+use chrono::{Duration, Local};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    ffi::CString,
+    fs::symlink_metadata,
+    os::unix::{ffi::OsStrExt, fs::MetadataExt},
+    path::{Path, PathBuf},
+};
+
+/// The number and the total size of files and directories.
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Usage {
+    count: usize,
+    size: u64,
+}
+
+impl Usage {
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns the total size in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn add(&mut self, size: u64) {
+        self.count += 1;
+        self.size += size;
+    }
+}
+
+/// How old files in trash are.
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct Ages {
+    today: Usage,
+    this_week: Usage,
+    older: Usage,
+}
+
+impl Ages {
+    /// Returns files trashed today.
+    pub fn today(&self) -> &Usage {
+        &self.today
+    }
+
+    /// Returns files trashed in the last 7 days, but not today.
+    pub fn this_week(&self) -> &Usage {
+        &self.this_week
+    }
+
+    /// Returns files trashed before, or whose time isn't known.
+    pub fn older(&self) -> &Usage {
+        &self.older
+    }
+}
+
+/// A file or a directory in trash with its size.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Entry {
+    #[serde(flatten)]
+    file_info: FileInfo,
+    size: u64,
+}
+
+impl Entry {
+    pub fn file_info(&self) -> &FileInfo {
+        &self.file_info
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+/// A summary of the trash.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    total: Usage,
+    largest: Vec<Entry>,
+    parents: BTreeMap<String, Usage>,
+    ages: Ages,
+    filesystems: BTreeMap<String, Usage>,
+}
+
+impl Stats {
+    /// Summarizes the list. Sizes of directories are computed recursively, and archived entries
+    /// count with their sizes before they were packed.
+    /// `largest` is how many of the largest entries to keep.
+    pub fn new(file_list: &FileList, largest: usize) -> Self {
+        let mut stats = Stats::default();
+        let now = Local::now();
+        let mut entries = vec![];
+        for i in file_list.files() {
+            let size = i.size();
+            stats.total.add(size);
+            let parent = Path::new(i.path())
+                .parent()
+                .unwrap_or_else(|| Path::new("/"));
+            stats
+                .parents
+                .entry(parent.display().to_string())
+                .or_default()
+                .add(size);
+            stats
+                .filesystems
+                .entry(mount_point(parent).display().to_string())
+                .or_default()
+                .add(size);
+            match i.deleted_at() {
+                Some(t) if t.date_naive() == now.date_naive() => stats.ages.today.add(size),
+                Some(t) if now.signed_duration_since(*t) < Duration::days(7) => {
+                    stats.ages.this_week.add(size)
+                }
+                _ => stats.ages.older.add(size),
+            }
+            entries.push(Entry {
+                file_info: i.clone(),
+                size,
+            });
+        }
+        entries.sort_by_key(|e| std::cmp::Reverse(e.size));
+        entries.truncate(largest);
+        stats.largest = entries;
+        stats
+    }
+
+    pub fn total(&self) -> &Usage {
+        &self.total
+    }
+
+    /// Returns the largest entries, largest first.
+    pub fn largest(&self) -> &Vec<Entry> {
+        &self.largest
+    }
+
+    /// Returns usage by the original parent directories.
+    pub fn parents(&self) -> &BTreeMap<String, Usage> {
+        &self.parents
+    }
+
+    pub fn ages(&self) -> &Ages {
+        &self.ages
+    }
+
+    /// Returns usage by the mount points of the filesystems files came from.
+    pub fn filesystems(&self) -> &BTreeMap<String, Usage> {
+        &self.filesystems
+    }
+}
+
+/// Returns the mount point of the filesystem a path is on.
+/// A path which no longer exists is looked up by its nearest existing ancestor.
+pub fn mount_point<P: AsRef<Path>>(path: P) -> PathBuf {
+    let mut path = path.as_ref();
+    let dev = loop {
+        match symlink_metadata(path) {
+            Ok(m) => break m.dev(),
+            Err(_) => match path.parent() {
+                Some(p) => path = p,
+                None => return PathBuf::from("/"),
+            },
+        }
+    };
+    while let Some(parent) = path.parent() {
+        match symlink_metadata(parent) {
+            Ok(m) if m.dev() == dev => path = parent,
+            _ => break,
+        }
+    }
+    path.to_path_buf()
+}
+
+/// Returns the space available to unprivileged users on the filesystem a path is on, in bytes.
+pub fn available<P: AsRef<Path>>(path: P) -> Option<u64> {
+    let path = CString::new(path.as_ref().as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
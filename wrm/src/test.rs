@@ -1,44 +1,677 @@
 #[cfg(test)]
 mod tests {
     use crate::{
-        actions::{clean, delete, remove, restore},
-        FileList, WrmPath,
+        actions::{asks_once, clean, compress, delete, purge, remove, restore},
+        report::{ALL_FAILED, SOME_FAILED, SUCCESS},
+        Options,
+    };
+    use chrono::{Duration, Local, TimeZone};
+    use filey::{create_dir, create_file, remove, Filey};
+    use std::{
+        env,
+        fs::{read_to_string, set_permissions, write, Permissions},
+        os::unix::fs::PermissionsExt,
+        path::{Path, PathBuf},
+        process::{self, Command},
+    };
+    use wrm::{
+        audit_log::{AuditLog, Operation, Outcome, Record},
+        file_list::{Version, LIST_VERSION},
+        git::{changes, parse_status},
+        hooks::Hooks,
+        stats::Stats,
+        utils::parse_time,
+        Error::{NotADirectory, UnsafeHooks},
+        FileList, Trash, WrmPath,
     };
-    use filey::{catenate, create, remove, FileTypes, Filey};
-    use std::path::Path;
 
     #[test]
     fn it_works() {
-        let wrm_path = WrmPath::default().expanded().unwrap();
-        let f = "test/a.txt";
-        let d = "test/a_dir";
-        let tf = Filey::new("~/.config/wrm/trash/a.txt")
-            .expand_user()
+        // Everything happens in a temporary directory, not in the real ~/.config/wrm.
+        let test = env::temp_dir().join(format!("wrm-test-{}", process::id()));
+        let wrm_path = WrmPath::new(test.join("wrm"));
+        let f = test.join("a.txt").display().to_string();
+        let d = test.join("a_dir").display().to_string();
+        let tf = Filey::new(wrm_path.trash().join("a.txt"));
+        let mut options = Options::new(true, true);
+        options.set_recursive(true);
+        remove!(&test);
+        create_dir!(&test, &d);
+        create_file!(&f);
+        Trash::new(&wrm_path).unwrap();
+        assert_eq!(FileList::read(wrm_path.list()).unwrap(), FileList::new());
+        remove(vec![f.to_string()], &wrm_path, &options).unwrap();
+        assert!(!Path::new(&f).exists());
+        assert!(tf.path().exists());
+        println!("{}", read_to_string(wrm_path.list()).unwrap());
+        delete(vec![d.to_string()], &wrm_path, &options).unwrap();
+        assert!(!Path::new(&d).exists());
+        restore(
+            vec![tf.to_string()],
+            &wrm_path,
+            None,
+            &Version::Latest,
+            &options,
+        )
+        .unwrap();
+        assert!(!tf.path().exists());
+        assert!(Path::new(&f).exists());
+        println!("{}", read_to_string(wrm_path.list()).unwrap());
+        remove(vec![f.to_string()], &wrm_path, &options).unwrap();
+        clean(&wrm_path, &options).unwrap();
+        assert!(!wrm_path.trash().exists());
+        assert!(!wrm_path.list().exists());
+        remove!(&test);
+    }
+
+    #[test]
+    fn migrate_list() {
+        let test = env::temp_dir().join(format!("wrm-test-migrate-{}", process::id()));
+        let wrm_path = WrmPath::new(&test);
+        remove!(&test);
+        create_dir!(&test);
+        let old = r#"{"files":[{"path":"/a.txt","path_trash":"/trash/a.txt"}]}"#;
+        write(wrm_path.list(), old).unwrap();
+        let file_list = Trash::new(&wrm_path).unwrap().list().unwrap();
+        assert_eq!(file_list.version(), LIST_VERSION);
+        assert_eq!(file_list.files()[0].path(), "/a.txt");
+        assert_eq!(read_to_string(test.join("list.json.v0.bak")).unwrap(), old);
+        assert!(read_to_string(wrm_path.list())
+            .unwrap()
+            .contains(&format!(r#""version": {}"#, LIST_VERSION)));
+        write(wrm_path.list(), r#"{"version":999,"files":[]}"#).unwrap();
+        assert!(Trash::new(&wrm_path).is_err());
+        remove!(&test);
+    }
+
+    #[test]
+    fn compress_and_restore() {
+        let test = env::temp_dir().join(format!("wrm-test-compress-{}", process::id()));
+        let wrm_path = WrmPath::new(test.join("wrm"));
+        let d = test.join("a_dir");
+        let f = d.join("a.txt");
+        let options = Options::new(true, true);
+        remove!(&test);
+        create_dir!(&test, &d);
+        write(&f, "hello").unwrap();
+        let trash = Trash::new(&wrm_path).unwrap();
+        let file_info = trash.put(&d, None).unwrap();
+        compress(&wrm_path, &Local::now(), &options).unwrap();
+        let archived = trash.list().unwrap().files()[0].clone();
+        assert!(archived.archived().is_some());
+        assert!(!Path::new(file_info.path_trash()).exists());
+        assert!(Path::new(&archived.archive_path()).exists());
+        // Sizes stay those of the files, not of the archive.
+        assert_eq!(archived.size(), 5);
+        assert_eq!(Stats::new(&trash.list().unwrap(), 1).total().size(), 5);
+        restore(
+            vec![file_info.path_trash().to_string()],
+            &wrm_path,
+            None,
+            &Version::Latest,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(read_to_string(&f).unwrap(), "hello");
+        assert!(!Path::new(&archived.archive_path()).exists());
+        assert!(trash.list().unwrap().files().is_empty());
+        remove!(&test);
+    }
+
+    #[test]
+    fn compress_beside_archive_name() {
+        let test = env::temp_dir().join(format!("wrm-test-archive-name-{}", process::id()));
+        let wrm_path = WrmPath::new(test.join("wrm"));
+        let f = test.join("foo");
+        let g = test.join("foo.tar.gz");
+        let options = Options::new(true, true);
+        remove!(&test);
+        create_dir!(&test);
+        write(&f, "foo").unwrap();
+        write(&g, "not an archive").unwrap();
+        let trash = Trash::new(&wrm_path).unwrap();
+        let foo = trash.put(&f, None).unwrap();
+        let archive = trash.put(&g, None).unwrap();
+        // The file named foo.tar.gz doesn't take the name foo's archive needs.
+        assert_ne!(archive.path_trash(), &foo.archive_path());
+        compress(&wrm_path, &Local::now(), &options).unwrap();
+        for i in [&foo, &archive] {
+            restore(
+                vec![i.path_trash().to_string()],
+                &wrm_path,
+                None,
+                &Version::Latest,
+                &options,
+            )
+            .unwrap();
+        }
+        assert_eq!(read_to_string(&f).unwrap(), "foo");
+        assert_eq!(read_to_string(&g).unwrap(), "not an archive");
+        remove!(&test);
+    }
+
+    #[test]
+    fn overwrite_moves_to_trash() {
+        let test = env::temp_dir().join(format!("wrm-test-overwrite-{}", process::id()));
+        let wrm_path = WrmPath::new(test.join("wrm"));
+        let d = test.join("d");
+        remove!(&test);
+        create_dir!(&test, &d);
+        write(d.join("f"), "old").unwrap();
+        let trash = Trash::new(&wrm_path).unwrap();
+        let old = trash.put(&d, None).unwrap();
+        create_dir!(&d);
+        write(d.join("g"), "precious").unwrap();
+        trash.restore(&old, None, true).unwrap();
+        assert_eq!(read_to_string(d.join("f")).unwrap(), "old");
+        let file_list = trash.list().unwrap();
+        let replaced = &file_list.files()[0];
+        assert_eq!(
+            read_to_string(Path::new(replaced.path_trash()).join("g")).unwrap(),
+            "precious"
+        );
+        remove!(&test);
+    }
+
+    #[test]
+    fn trash_itself() {
+        let test = env::temp_dir().join(format!("wrm-test-itself-{}", process::id()));
+        let wrm_path = WrmPath::new(test.join("wrm"));
+        let f = test.join("a.txt");
+        let options = Options::new(true, true);
+        remove!(&test);
+        create_dir!(&test);
+        create_file!(&f);
+        let trash = Trash::new(&wrm_path).unwrap();
+        let file_info = trash.put(&f, None).unwrap();
+        let paths = vec![
+            test.display().to_string(),
+            wrm_path.trash().display().to_string(),
+            wrm_path.list().display().to_string(),
+        ];
+        remove(paths, &wrm_path, &options).unwrap();
+        assert!(wrm_path.list().exists());
+        assert!(Path::new(file_info.path_trash()).exists());
+        // Files in trash are purged instead.
+        remove(
+            vec![file_info.path_trash().to_string()],
+            &wrm_path,
+            &options,
+        )
+        .unwrap();
+        assert!(!Path::new(file_info.path_trash()).exists());
+        assert!(trash.list().unwrap().files().is_empty());
+        remove!(&test);
+    }
+
+    #[test]
+    fn restore_to() {
+        let test = env::temp_dir().join(format!("wrm-test-restore-to-{}", process::id()));
+        let wrm_path = WrmPath::new(test.join("wrm"));
+        let f = test.join("a.txt").display().to_string();
+        let g = test.join("b.txt").display().to_string();
+        let dest = test.join("dest");
+        let options = Options::new(true, true);
+        remove!(&test);
+        create_dir!(&test, &dest);
+        write(&f, "a").unwrap();
+        write(&g, "b").unwrap();
+        remove(vec![f.clone(), g.clone()], &wrm_path, &options).unwrap();
+        // A missing directory isn't taken as a new name, like mv(1).
+        let trash = Trash::new(&wrm_path).unwrap();
+        let entry = trash.list().unwrap().files()[0].clone();
+        let missing = test.join("missing/");
+        assert!(matches!(
+            Trash::destination(&entry, Some(&missing)),
+            Err(NotADirectory { .. })
+        ));
+        let report = restore(
+            vec![f.clone()],
+            &wrm_path,
+            Some(&format!("{}/", test.join("missing").display())),
+            &Version::Latest,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(report.exit_code(), ALL_FAILED);
+        assert!(!test.join("missing").exists());
+        // Into a directory, keeping the name
+        let dest_dir = dest.display().to_string();
+        restore(
+            vec![f.clone()],
+            &wrm_path,
+            Some(&dest_dir),
+            &Version::Latest,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(read_to_string(dest.join("a.txt")).unwrap(), "a");
+        assert!(!Path::new(&f).exists());
+        // To a new name
+        let renamed = dest.join("c.txt").display().to_string();
+        restore(
+            vec![g.clone()],
+            &wrm_path,
+            Some(&renamed),
+            &Version::Latest,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(read_to_string(&renamed).unwrap(), "b");
+        assert!(!Path::new(&g).exists());
+        assert!(Trash::new(&wrm_path)
+            .unwrap()
+            .list()
+            .unwrap()
+            .files()
+            .is_empty());
+        remove!(&test);
+    }
+
+    #[test]
+    fn restore_directory_over_file() {
+        let test = env::temp_dir().join(format!("wrm-test-dir-over-file-{}", process::id()));
+        let wrm_path = WrmPath::new(test.join("wrm"));
+        let d = test.join("d");
+        let file = test.join("file").display().to_string();
+        let mut options = Options::new(true, true);
+        options.set_recursive(true);
+        remove!(&test);
+        create_dir!(&test, &d);
+        write(d.join("a"), "a").unwrap();
+        write(&file, "file").unwrap();
+        remove(vec![d.display().to_string()], &wrm_path, &options).unwrap();
+        let report = restore(
+            vec![d.display().to_string()],
+            &wrm_path,
+            Some(&file),
+            &Version::Latest,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(report.exit_code(), ALL_FAILED);
+        assert_eq!(read_to_string(&file).unwrap(), "file");
+        assert_eq!(
+            Trash::new(&wrm_path).unwrap().list().unwrap().files().len(),
+            1
+        );
+        remove!(&test);
+    }
+
+    #[test]
+    fn restore_version() {
+        let test = env::temp_dir().join(format!("wrm-test-version-{}", process::id()));
+        let wrm_path = WrmPath::new(test.join("wrm"));
+        let f = test.join("config.yaml").display().to_string();
+        let options = Options::new(true, true);
+        remove!(&test);
+        create_dir!(&test);
+        let mut times = vec![];
+        for content in ["1", "2", "3"] {
+            write(&f, content).unwrap();
+            remove(vec![f.clone()], &wrm_path, &options).unwrap();
+            times.push(Local::now());
+        }
+        restore(
+            vec![f.clone()],
+            &wrm_path,
+            None,
+            &Version::Number(2),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(read_to_string(&f).unwrap(), "2");
+        remove!(&f);
+        restore(
+            vec![f.clone()],
+            &wrm_path,
+            None,
+            &Version::At(times[0]),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(read_to_string(&f).unwrap(), "1");
+        remove!(&f);
+        restore(vec![f.clone()], &wrm_path, None, &Version::Latest, &options).unwrap();
+        assert_eq!(read_to_string(&f).unwrap(), "3");
+        remove!(&f);
+        // Nothing was trashed at the time.
+        let before = times[0] - Duration::try_days(1).unwrap();
+        write(&f, "4").unwrap();
+        remove(vec![f.clone()], &wrm_path, &options).unwrap();
+        let report = restore(
+            vec![f.clone()],
+            &wrm_path,
+            None,
+            &Version::At(before),
+            &options,
+        );
+        assert!(report.map(|r| r.exit_code() != 0).unwrap_or(true));
+        assert!(!Path::new(&f).exists());
+        remove!(&test);
+    }
+
+    #[test]
+    fn parse_times() {
+        let local = |y, m, d, h, min, s| Local.with_ymd_and_hms(y, m, d, h, min, s).unwrap();
+        assert_eq!(
+            parse_time("2024-01-31T12:00:00+00:00").unwrap(),
+            chrono::Utc.with_ymd_and_hms(2024, 1, 31, 12, 0, 0).unwrap()
+        );
+        assert_eq!(
+            parse_time("2024-01-31 12:34:56").unwrap(),
+            local(2024, 1, 31, 12, 34, 56)
+        );
+        assert_eq!(
+            parse_time("2024-01-31 12:34").unwrap(),
+            local(2024, 1, 31, 12, 34, 0)
+        );
+        assert_eq!(
+            parse_time(" 2024-01-31 ").unwrap(),
+            local(2024, 1, 31, 0, 0, 0)
+        );
+        for (time, duration) in [
+            ("30s", Duration::try_seconds(30)),
+            ("15m", Duration::try_minutes(15)),
+            ("2h", Duration::try_hours(2)),
+            ("7d", Duration::try_days(7)),
+            ("2w", Duration::try_weeks(2)),
+        ] {
+            let expected = Local::now() - duration.unwrap();
+            let parsed = parse_time(time).unwrap();
+            assert!((parsed - expected).num_seconds().abs() <= 1, "{}", time);
+        }
+        for time in [
+            "",
+            "yesterday",
+            "2h ago",
+            "7y",
+            "d",
+            "-2h",
+            "2024-13-01",
+            "99999999999999w",
+        ] {
+            assert!(parse_time(time).is_err(), "{}", time);
+        }
+    }
+
+    #[test]
+    fn parse_git_status() {
+        let root = Path::new("/repo");
+        let status = b" M src/main.rs\0A  new.rs\0R  renamed.rs\0old.rs\0?? notes/todo.txt\0!! target/x\0D  gone.rs\0";
+        let changes = parse_status(root, status);
+        assert_eq!(
+            changes.modified(),
+            &vec![
+                root.join("src/main.rs"),
+                root.join("new.rs"),
+                root.join("renamed.rs"),
+                root.join("gone.rs"),
+            ]
+        );
+        assert_eq!(changes.untracked(), &vec![root.join("notes/todo.txt")]);
+        assert!(parse_status(root, b"").is_empty());
+    }
+
+    #[test]
+    fn git_changes() {
+        let test = env::temp_dir().join(format!("wrm-test-git-{}", process::id()));
+        remove!(&test);
+        create_dir!(&test);
+        let test = test.canonicalize().unwrap();
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .arg("-C")
+                .arg(&test)
+                .args(["-c", "user.name=wrm", "-c", "user.email=wrm@example.com"])
+                .args(args)
+                .output()
+                .map(|o| o.status.success())
+                .unwrap_or(false)
+        };
+        // Without git, there is nothing to check.
+        if !git(&["init", "-q"]) {
+            remove!(&test);
+            return;
+        }
+        // Outside a working tree
+        assert_eq!(changes(env::temp_dir().join("wrm-test-no-such-file")), None);
+        write(test.join("clean.txt"), "a").unwrap();
+        write(test.join("edited.txt"), "a").unwrap();
+        assert!(git(&["add", "."]));
+        assert!(git(&["commit", "-q", "-m", "init"]));
+        assert!(changes(test.join("clean.txt")).unwrap().is_empty());
+        write(test.join("edited.txt"), "b").unwrap();
+        create_dir!(test.join("new"));
+        write(test.join("new/file.txt"), "c").unwrap();
+        let found = changes(&test).unwrap();
+        assert_eq!(found.modified(), &vec![test.join("edited.txt")]);
+        assert_eq!(found.untracked(), &vec![test.join("new/file.txt")]);
+        remove!(&test);
+    }
+
+    #[test]
+    fn unsafe_hooks() {
+        let test = env::temp_dir().join(format!("wrm-test-hooks-{}", process::id()));
+        let wrm_path = WrmPath::new(test.join("wrm"));
+        remove!(&test);
+        Trash::new(&wrm_path).unwrap();
+        write(wrm_path.hooks(), r#"{"pre_clean": "true"}"#).unwrap();
+        set_permissions(wrm_path.hooks(), Permissions::from_mode(0o644)).unwrap();
+        assert!(Hooks::read(wrm_path.hooks()).is_ok());
+        set_permissions(wrm_path.hooks(), Permissions::from_mode(0o664)).unwrap();
+        assert!(matches!(
+            Hooks::read(wrm_path.hooks()),
+            Err(UnsafeHooks { .. })
+        ));
+        set_permissions(wrm_path.hooks(), Permissions::from_mode(0o646)).unwrap();
+        assert!(matches!(
+            Hooks::read(wrm_path.hooks()),
+            Err(UnsafeHooks { .. })
+        ));
+        remove!(&test);
+    }
+
+    #[test]
+    fn failed_pre_clean_hook() {
+        let test = env::temp_dir().join(format!("wrm-test-clean-hook-{}", process::id()));
+        let wrm_path = WrmPath::new(test.join("wrm"));
+        let f = test.join("a.txt").display().to_string();
+        let options = Options::new(true, true);
+        remove!(&test);
+        create_dir!(&test);
+        write(&f, "a").unwrap();
+        remove(vec![f.clone()], &wrm_path, &options).unwrap();
+        write(wrm_path.hooks(), r#"{"pre_clean": "exit 1"}"#).unwrap();
+        let report = clean(&wrm_path, &options).unwrap();
+        assert_eq!(report.exit_code(), ALL_FAILED);
+        assert_eq!(
+            Trash::new(&wrm_path).unwrap().list().unwrap().files().len(),
+            1
+        );
+        let log = read_to_string(wrm_path.log()).unwrap();
+        let last: serde_json::Value = serde_json::from_str(log.lines().last().unwrap()).unwrap();
+        assert_eq!(last["action"], "clean");
+        assert_eq!(last["outcome"], "failed");
+        assert_eq!(last["path"], f.as_str());
+        remove!(&test);
+    }
+
+    #[test]
+    fn purge_keeps_pinned() {
+        let test = env::temp_dir().join(format!("wrm-test-pinned-{}", process::id()));
+        let wrm_path = WrmPath::new(test.join("wrm"));
+        let options = Options::new(true, true);
+        remove!(&test);
+        create_dir!(&test);
+        let trash = Trash::new(&wrm_path).unwrap();
+        let mut trashed = vec![];
+        for name in ["a", "b", "c"] {
+            write(test.join(name), name).unwrap();
+            trashed.push(trash.put(test.join(name), None).unwrap());
+        }
+        trash.set_pinned(&trashed[0], true).unwrap();
+        // Skipping a pinned one isn't a failure.
+        let report = purge(
+            vec![trashed[0].path_trash().to_string()],
+            &wrm_path,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(report.exit_code(), SUCCESS);
+        assert!(Path::new(trashed[0].path_trash()).exists());
+        let paths = trashed.iter().take(2).map(|i| i.path_trash().to_string());
+        purge(paths.collect(), &wrm_path, &options).unwrap();
+        assert!(Path::new(trashed[0].path_trash()).exists());
+        assert!(!Path::new(trashed[1].path_trash()).exists());
+        let emptied = trash.empty(false).unwrap();
+        assert_eq!(emptied.len(), 1);
+        assert_eq!(emptied[0].path_trash(), trashed[2].path_trash());
+        let file_list = trash.list().unwrap();
+        assert_eq!(file_list.files().len(), 1);
+        assert!(file_list.files()[0].pinned());
+        assert!(Path::new(trashed[0].path_trash()).exists());
+        trash.empty(true).unwrap();
+        assert!(trash.list().unwrap().files().is_empty());
+        remove!(&test);
+    }
+
+    #[test]
+    fn directories_like_rm() {
+        let test = env::temp_dir().join(format!("wrm-test-rm-{}", process::id()));
+        let wrm_path = WrmPath::new(test.join("wrm"));
+        let empty = test.join("empty").display().to_string();
+        let full = test.join("full").display().to_string();
+        remove!(&test);
+        create_dir!(&test, &empty, &full);
+        write(test.join("full/a"), "a").unwrap();
+        let mut options = Options::new(true, true);
+        // Without -R or --dir, directories fail.
+        for action in [remove, delete] {
+            let report = action(vec![empty.clone()], &wrm_path, &options).unwrap();
+            assert_eq!(report.exit_code(), ALL_FAILED);
+            assert!(Path::new(&empty).is_dir());
+        }
+        // --dir removes only empty ones.
+        options.set_dir(true);
+        let report = remove(vec![full.clone()], &wrm_path, &options).unwrap();
+        assert_eq!(report.exit_code(), ALL_FAILED);
+        assert!(Path::new(&full).is_dir());
+        let report = delete(vec![full.clone(), empty.clone()], &wrm_path, &options).unwrap();
+        assert_eq!(report.exit_code(), SOME_FAILED);
+        assert!(!Path::new(&empty).exists());
+        assert!(Path::new(&full).is_dir());
+        // -R removes any.
+        options.set_recursive(true);
+        let report = remove(vec![full.clone()], &wrm_path, &options).unwrap();
+        assert_eq!(report.exit_code(), SUCCESS);
+        assert!(!Path::new(&full).exists());
+        remove!(&test);
+    }
+
+    #[test]
+    fn interactive_once() {
+        let test = env::temp_dir().join(format!("wrm-test-once-{}", process::id()));
+        remove!(&test);
+        create_dir!(&test, test.join("d"));
+        let files: Vec<String> = ["a", "b", "c", "e"]
+            .iter()
+            .map(|i| test.join(i).display().to_string())
+            .collect();
+        for i in &files {
+            write(i, i).unwrap();
+        }
+        let dir = test.join("d").display().to_string();
+        let mut options = Options::new(false, true);
+        assert!(!asks_once(&files, &options));
+        options.set_interactive_once(true);
+        assert!(!asks_once(&files[..3], &options));
+        assert!(asks_once(&files, &options));
+        assert!(asks_once(std::slice::from_ref(&dir), &options));
+        assert!(asks_once(&[files[0].clone(), dir.clone()], &options));
+        // -n never prompts.
+        let mut options = Options::new(true, true);
+        options.set_interactive_once(true);
+        assert!(!asks_once(&files, &options));
+        remove!(&test);
+    }
+
+    #[test]
+    fn audit_log() {
+        let test = env::temp_dir().join(format!("wrm-test-audit-{}", process::id()));
+        let wrm_path = WrmPath::new(test.join("wrm"));
+        let f = test.join("a.txt").display().to_string();
+        let g = test.join("sub/b.txt").display().to_string();
+        let options = Options::new(true, true);
+        remove!(&test);
+        create_dir!(&test, test.join("sub"));
+        let audit_log = AuditLog::new(wrm_path.log());
+        // Each action adds exactly one record.
+        let mut count = 0;
+        let mut logged = |action: Operation, outcome: Outcome, path: &str| {
+            let records = audit_log.read().unwrap();
+            assert_eq!(records.len(), count + 1);
+            count += 1;
+            let last = records.last().unwrap();
+            assert_eq!(last.action(), action);
+            assert_eq!(last.outcome(), outcome);
+            assert_eq!(last.path(), path);
+        };
+        write(&f, "a").unwrap();
+        remove(vec![f.clone()], &wrm_path, &options).unwrap();
+        logged(Operation::Trash, Outcome::Succeeded, &f);
+        let start = Local::now();
+        restore(vec![f.clone()], &wrm_path, None, &Version::Latest, &options).unwrap();
+        logged(Operation::Restore, Outcome::Succeeded, &f);
+        // The destination exists, and isn't overwritten with -n.
+        remove(vec![f.clone()], &wrm_path, &options).unwrap();
+        logged(Operation::Trash, Outcome::Succeeded, &f);
+        write(&f, "new").unwrap();
+        restore(vec![f.clone()], &wrm_path, None, &Version::Latest, &options).unwrap();
+        logged(Operation::Restore, Outcome::Failed, &f);
+        let path_trash = Trash::new(&wrm_path).unwrap().list().unwrap().files()[0]
+            .path_trash()
+            .to_string();
+        purge(vec![path_trash], &wrm_path, &options).unwrap();
+        logged(Operation::Purge, Outcome::Succeeded, &f);
+        let end = Local::now();
+        delete(vec![f.clone()], &wrm_path, &options).unwrap();
+        logged(Operation::Delete, Outcome::Succeeded, &f);
+        write(&g, "b").unwrap();
+        remove(vec![g.clone()], &wrm_path, &options).unwrap();
+        logged(Operation::Trash, Outcome::Succeeded, &g);
+        clean(&wrm_path, &options).unwrap();
+        logged(Operation::Clean, Outcome::Succeeded, &g);
+        // Filters of --log
+        let actions = |records: Vec<Record>| -> Vec<Operation> {
+            records.iter().map(|r| r.action()).collect()
+        };
+        assert_eq!(audit_log.query(&[], None, None).unwrap().len(), 8);
+        assert_eq!(
+            actions(audit_log.query(&[], Some(&start), Some(&end)).unwrap()),
+            vec![
+                Operation::Restore,
+                Operation::Trash,
+                Operation::Restore,
+                Operation::Purge
+            ]
+        );
+        assert_eq!(
+            actions(audit_log.query(&[], Some(&end), None).unwrap()),
+            vec![Operation::Delete, Operation::Trash, Operation::Clean]
+        );
+        assert_eq!(audit_log.query(&[], None, Some(&start)).unwrap().len(), 1);
+        let sub = audit_log.query(&[test.join("sub")], None, None).unwrap();
+        assert_eq!(actions(sub), vec![Operation::Trash, Operation::Clean]);
+        let file = audit_log
+            .query(&[PathBuf::from(&f)], Some(&end), None)
             .unwrap();
-        remove!("test", wrm_path.dir());
-        create!(
-            FileTypes::Directory,
-            "test",
-            wrm_path.dir(),
-            wrm_path.trash(),
-            &d,
-        );
-        create!(FileTypes::File, wrm_path.list(), &f);
-        FileList::new().write(wrm_path.list()).unwrap();
-        remove(vec![f.to_string()], &wrm_path, true, false).unwrap();
-        assert_eq!(Path::new(&f).exists(), false);
-        assert_eq!(tf.path().exists(), true);
-        println!("{}", catenate!(wrm_path.list()));
-        delete(vec![d.to_string()], &wrm_path, true, false).unwrap();
-        assert_eq!(Path::new(&d).exists(), false);
-        restore(vec![tf.to_string()], &wrm_path, true, false).unwrap();
-        assert_eq!(tf.path().exists(), false);
-        assert_eq!(Path::new(&f).exists(), true);
-        println!("{}", catenate!(wrm_path.list()));
-        remove(vec![f.to_string()], &wrm_path, true, false).unwrap();
-        clean(&wrm_path, true, false).unwrap();
-        assert_eq!(Path::new(wrm_path.trash()).exists(), false);
-        assert_eq!(Path::new(wrm_path.list()).exists(), false);
-        remove!("test", wrm_path.dir());
+        assert_eq!(actions(file), vec![Operation::Delete]);
+        // A path is matched by components, not as a string prefix.
+        assert!(audit_log
+            .query(&[test.join("su")], None, None)
+            .unwrap()
+            .is_empty());
+        remove!(&test);
     }
 }
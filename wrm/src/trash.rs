@@ -0,0 +1,539 @@
+use crate::{
+    audit_log::{AuditLog, Operation, Record},
+    file_list::{Archived, FileInfo, FileList, Owner},
+    utils::{is_root, size},
+    wrm_path::WrmPath,
+    Error::{
+        AlreadyExists, ContainsTrash, InTrash, NotADirectory, NotFoundError, Pinned, WrmError,
+    },
+    Result,
+};
+use chrono::{DateTime, Local};
+use filey::{Error::GetFileNameError, FileTypes, Filey};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use std::{
+    fs::{create_dir_all, remove_file, set_permissions, File, OpenOptions, Permissions},
+    io::ErrorKind,
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{lchown, PermissionsExt},
+    },
+    path::{Path, PathBuf},
+};
+use tar::{Archive, Builder};
+
+/// A trash directory.
+///
+/// Its root is laid out as described in [`WrmPath`].
+///
+/// # Examples
+/// ```no_run
+/// # fn main() -> wrm::Result<()> {
+/// use wrm::Trash;
+///
+/// let trash = Trash::new("/tmp/wrm")?;
+/// let file_info = trash.put("old.txt", Some("replaced by new.txt".to_string()))?;
+/// trash.restore(&file_info, None, false)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Trash {
+    path: WrmPath,
+}
+
+impl Trash {
+    /// Opens the trash in the given root directory.
+    /// The directory, `trash/` and `list.json` are created if they don't exist,
+    /// and `list.json` of an older version is migrated(see [`FileList::migrate`]).
+    pub fn new<P: AsRef<Path>>(root: P) -> Result<Self> {
+        let trash = Trash {
+            path: WrmPath::new(root),
+        };
+        create_dir_all(trash.trash_dir())
+            .map_err(|e| e.into())
+            .map_err(WrmError)?;
+        if trash.list_path().exists() {
+            FileList::migrate(trash.list_path())?;
+        } else {
+            FileList::new().write(trash.list_path())?;
+        }
+        Ok(trash)
+    }
+
+    pub fn path(&self) -> &WrmPath {
+        &self.path
+    }
+
+    fn trash_dir(&self) -> PathBuf {
+        self.path.trash()
+    }
+
+    fn list_path(&self) -> PathBuf {
+        self.path.list()
+    }
+
+    /// Returns all files and directories in trash.
+    pub fn list(&self) -> Result<FileList> {
+        if self.list_path().exists() {
+            FileList::read(self.list_path())
+        } else {
+            Ok(FileList::new())
+        }
+    }
+
+    /// Moves a file or a directory to trash.
+    pub fn put<P: AsRef<Path>>(&self, path: P, note: Option<String>) -> Result<FileInfo> {
+        let target = absolutize(path)?;
+        if !target.exists() {
+            return Err(NotFoundError {
+                path: target.to_string(),
+            });
+        }
+        self.check_target(target.path())?;
+        let mut file_info = FileInfo::new(target.path(), &self.path)?;
+        file_info.set_note(note);
+        let mut record = Record::new(Operation::Trash, target.path(), size(target.path()));
+        record.set_path_trash(file_info.path_trash());
+        self.logged(&mut record, || {
+            create_dir_all(self.trash_dir())
+                .map_err(|e| e.into())
+                .map_err(WrmError)?;
+            let mut moved = target.clone();
+            moved
+                .move_to(file_info.path_trash())
+                .map_err(|e| e.into())
+                .map_err(WrmError)?;
+            let written = self
+                .list()
+                .and_then(|mut l| l.add(&file_info).write(self.list_path()));
+            if written.is_err() {
+                // Move it back rather than leaving it in trash without an entry.
+                let _ = moved.move_to(target.path());
+            }
+            written
+        })?;
+        Ok(file_info)
+    }
+
+    /// Deletes a file or a directory permanently without moving it to trash.
+    pub fn delete<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let target = absolutize(path)?;
+        if !target.exists() {
+            return Err(NotFoundError {
+                path: target.to_string(),
+            });
+        }
+        self.check_target(target.path())?;
+        let mut record = Record::new(Operation::Delete, target.path(), size(target.path()));
+        self.logged(&mut record, || {
+            target.remove().map_err(|e| e.into()).map_err(WrmError)
+        })
+    }
+
+    /// Checks that a path can be moved to trash or deleted.
+    ///
+    /// The trash root, its ancestors and files in it are refused, because removing them would
+    /// move the trash into itself or remove `list.json` out from under wrm.
+    /// Files in trash are purged with [`Trash::purge`] instead(see [`Trash::entry_at`]).
+    pub fn check_target<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let given = path.as_ref().display().to_string();
+        let path = resolve(path.as_ref());
+        for root in self.roots() {
+            if root.join("trash").starts_with(&path) {
+                return Err(ContainsTrash { path: given });
+            }
+            if path.starts_with(&root) {
+                return Err(InTrash { path: given });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the entry a path in trash is, by its path in trash or its archive.
+    /// Paths under an entry, or elsewhere, return None.
+    pub fn entry_at<P: AsRef<Path>>(&self, path: P) -> Result<Option<FileInfo>> {
+        let path = resolve(path.as_ref());
+        let relative = match self
+            .roots()
+            .iter()
+            .find_map(|r| path.strip_prefix(r.join("trash")).ok())
+        {
+            Some(r) => self.trash_dir().join(r).display().to_string(),
+            None => return Ok(None),
+        };
+        Ok(self
+            .list()?
+            .files()
+            .iter()
+            .find(|i| *i.path_trash() == relative || i.archive_path() == relative)
+            .cloned())
+    }
+
+    // The trash root as given, and with symbolic links resolved.
+    fn roots(&self) -> Vec<PathBuf> {
+        let root = resolve(self.path.dir());
+        match root.canonicalize() {
+            Ok(canonical) if canonical != root => vec![root, canonical],
+            _ => vec![root],
+        }
+    }
+
+    /// Restores a file or a directory in trash and returns where it was restored to.
+    ///
+    /// It is restored to where it came from, or to `to` like mv(1): into `to` if it is a
+    /// directory, otherwise to `to` itself. An existing destination is replaced only if
+    /// `overwrite` is true, and is moved to trash then. The recorded mode is put back, and so is
+    /// the owner if run by root. An archived one is unpacked first.
+    pub fn restore(
+        &self,
+        file_info: &FileInfo,
+        to: Option<&Path>,
+        overwrite: bool,
+    ) -> Result<PathBuf> {
+        if !Filey::new(file_info.stored_path()).exists() {
+            return Err(NotFoundError {
+                path: file_info.path_trash().to_string(),
+            });
+        }
+        let destination = Trash::destination(file_info, to)?;
+        let mut record = Record::new(Operation::Restore, &destination, file_info.size());
+        record.set_path_trash(file_info.path_trash());
+        let exists = Filey::new(&destination).exists();
+        if exists && !overwrite {
+            let e = AlreadyExists {
+                path: destination.display().to_string(),
+            };
+            self.log(record.set_error(&e))?;
+            return Err(e);
+        }
+        self.logged(&mut record, || {
+            let file_info = &self.unpack(file_info)?;
+            let mut target = absolutize(file_info.path_trash())?;
+            if exists {
+                // The replaced one can be restored in turn.
+                self.put(
+                    &destination,
+                    Some(format!("replaced by restoring {}", file_info.path_trash())),
+                )?;
+            }
+            target
+                .move_to(&destination)
+                .map_err(|e| e.into())
+                .map_err(WrmError)?;
+            self.list()?.remove(file_info).write(self.list_path())?;
+            match file_info.owner() {
+                Some(owner) => restore_owner(&destination, owner),
+                None => Ok(()),
+            }
+        })?;
+        Ok(destination)
+    }
+
+    /// Returns where a file in trash is restored to(see [`Trash::restore`]).
+    ///
+    /// Like mv(1), `to` is a directory to restore into if it exists as one. A `to` ending with
+    /// `/` must be an existing directory, and a directory can't replace an existing file.
+    pub fn destination(file_info: &FileInfo, to: Option<&Path>) -> Result<PathBuf> {
+        let destination = match to {
+            None => return Ok(absolutize(file_info.path())?.path().to_path_buf()),
+            Some(to) => {
+                let given = to;
+                let to = absolutize(to)?;
+                if to.is_dir() {
+                    let original = Filey::new(file_info.path());
+                    let file_name = original
+                        .file_name()
+                        .ok_or_else(|| GetFileNameError {
+                            path: original.to_string(),
+                        })
+                        .map_err(|e| e.into())
+                        .map_err(WrmError)?;
+                    to.path().join(file_name)
+                } else if given.as_os_str().as_bytes().ends_with(b"/") {
+                    return Err(NotADirectory {
+                        path: given.display().to_string(),
+                    });
+                } else {
+                    to.path().to_path_buf()
+                }
+            }
+        };
+        let is_dir = match file_info.archived() {
+            Some(archived) => archived.file_type() == FileTypes::Directory,
+            None => {
+                let path = Path::new(file_info.path_trash());
+                path.is_dir() && !path.is_symlink()
+            }
+        };
+        let replaced = Filey::new(&destination);
+        if is_dir && replaced.exists() && replaced.file_type() != Some(FileTypes::Directory) {
+            return Err(NotADirectory {
+                path: destination.display().to_string(),
+            });
+        }
+        Ok(destination)
+    }
+
+    /// Deletes a file or a directory in trash permanently.
+    /// Pinned files and directories are refused.
+    pub fn purge(&self, file_info: &FileInfo) -> Result<()> {
+        if file_info.pinned() {
+            return Err(Pinned {
+                path: file_info.path_trash().to_string(),
+            });
+        }
+        self.remove_entry(file_info, Operation::Purge)
+    }
+
+    /// Deletes all files and directories in trash permanently and returns them.
+    ///
+    /// Pinned ones are kept unless `include_pinned` is true. If they aren't kept,
+    /// `trash/` and `list.json` themselves are deleted, and created again when needed.
+    pub fn empty(&self, include_pinned: bool) -> Result<Vec<FileInfo>> {
+        let file_list = self.list()?;
+        if !include_pinned {
+            let targets = file_list.unpinned();
+            for i in targets.files() {
+                self.remove_entry(i, Operation::Clean)?;
+            }
+            return Ok(targets.files().clone());
+        }
+        let mut records: Vec<Record> = file_list
+            .files()
+            .iter()
+            .map(|i| {
+                let mut record = Record::new(Operation::Clean, i.path(), i.size());
+                record.set_path_trash(i.path_trash());
+                record
+            })
+            .collect();
+        let result = [self.trash_dir(), self.list_path()]
+            .iter()
+            .map(Filey::new)
+            .filter(|f| f.exists())
+            .try_for_each(|f| f.remove().map_err(|e| e.into()).map_err(WrmError));
+        for record in records.iter_mut() {
+            if let Err(e) = &result {
+                record.set_error(e);
+            }
+            self.log(record)?;
+        }
+        result?;
+        Ok(file_list.files().clone())
+    }
+
+    fn remove_entry(&self, file_info: &FileInfo, operation: Operation) -> Result<()> {
+        let target = absolutize(file_info.stored_path())?;
+        let mut record = Record::new(operation, file_info.path(), file_info.size());
+        record.set_path_trash(file_info.path_trash());
+        self.logged(&mut record, || {
+            if target.exists() {
+                target.remove().map_err(|e| e.into()).map_err(WrmError)?;
+            }
+            self.list()?.remove(file_info).write(self.list_path())
+        })
+    }
+
+    /// Pins or unpins a file or a directory in trash.
+    /// Pinned ones are never deleted by purging.
+    pub fn set_pinned(&self, file_info: &FileInfo, value: bool) -> Result<()> {
+        self.update(file_info, |i| {
+            i.set_pinned(value);
+        })?;
+        Ok(())
+    }
+
+    /// Packs files and directories trashed before the time into compressed archives in trash,
+    /// and returns them. Ones trashed by old versions of wrm, whose time isn't known, are packed too.
+    pub fn compress(&self, before: &DateTime<Local>) -> Result<Vec<FileInfo>> {
+        let mut packed = vec![];
+        for i in self.list()?.files() {
+            if i.archived().is_none() && i.deleted_at().map(|t| t < before).unwrap_or(true) {
+                packed.push(self.pack(i)?);
+            }
+        }
+        Ok(packed)
+    }
+
+    /// Packs a file or a directory in trash into a compressed archive next to it,
+    /// and returns the updated entry.
+    pub fn pack(&self, file_info: &FileInfo) -> Result<FileInfo> {
+        if file_info.archived().is_some() {
+            return Ok(file_info.clone());
+        }
+        let target = absolutize(file_info.path_trash())?;
+        let file_type = target.file_type().ok_or_else(|| NotFoundError {
+            path: target.to_string(),
+        })?;
+        let archived = Archived::new(file_type, size(target.path()));
+        let archive_path = file_info.archive_path();
+        write_archive(target.path(), Path::new(&archive_path))?;
+        target.remove().map_err(|e| e.into()).map_err(WrmError)?;
+        self.update(file_info, |i| {
+            i.set_archived(Some(archived));
+        })
+    }
+
+    /// Unpacks an archived file or directory back to its path in trash,
+    /// and returns the updated entry.
+    pub fn unpack(&self, file_info: &FileInfo) -> Result<FileInfo> {
+        if file_info.archived().is_none() {
+            return Ok(file_info.clone());
+        }
+        read_archive(Path::new(&file_info.archive_path()), &self.trash_dir())?;
+        remove_file(file_info.archive_path())
+            .map_err(|e| e.into())
+            .map_err(WrmError)?;
+        self.update(file_info, |i| {
+            i.set_archived(None);
+        })
+    }
+
+    /// Extracts a copy of an archived file or directory into `dir` leaving the archive as it is,
+    /// and returns the path to the copy.
+    pub fn extract<P: AsRef<Path>>(&self, file_info: &FileInfo, dir: P) -> Result<PathBuf> {
+        read_archive(Path::new(&file_info.archive_path()), dir.as_ref())?;
+        let file_name = Filey::new(file_info.path_trash())
+            .file_name()
+            .ok_or_else(|| GetFileNameError {
+                path: file_info.path_trash().to_string(),
+            })
+            .map_err(|e| e.into())
+            .map_err(WrmError)?;
+        Ok(dir.as_ref().join(file_name))
+    }
+
+    // Change an entry in list.json and return the changed one.
+    fn update<F: FnOnce(&mut FileInfo)>(&self, file_info: &FileInfo, f: F) -> Result<FileInfo> {
+        let mut file_list = self.list()?;
+        let entry = file_list
+            .find_mut(file_info.path_trash())
+            .ok_or_else(|| NotFoundError {
+                path: file_info.path_trash().to_string(),
+            })?;
+        f(entry);
+        let entry = entry.clone();
+        file_list.write(self.list_path())?;
+        Ok(entry)
+    }
+
+    /// Appends a record to the audit log.
+    pub fn log(&self, record: &Record) -> Result<()> {
+        AuditLog::new(self.path.log()).append(record)
+    }
+
+    // Do an operation and record the outcome in the audit log.
+    fn logged<F: FnOnce() -> Result<()>>(&self, record: &mut Record, operation: F) -> Result<()> {
+        match operation() {
+            Ok(()) => self.log(record),
+            Err(e) => {
+                self.log(record.set_error(&e))?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Removes files and directories which are no longer in trash from list.json.
+    pub fn check(&self) -> Result<()> {
+        let mut file_list = self.list()?;
+        let missing: Vec<FileInfo> = file_list
+            .files()
+            .iter()
+            .filter(|i| !Filey::new(i.stored_path()).exists())
+            .cloned()
+            .collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        for i in &missing {
+            file_list.remove(i);
+        }
+        file_list.write(self.list_path())
+    }
+}
+
+// Pack a file or a directory into a tar.gz archive. Symbolic links are kept as they are.
+fn write_archive(path: &Path, archive_path: &Path) -> Result<()> {
+    let file_name = path.file_name().ok_or_else(|| NotFoundError {
+        path: path.display().to_string(),
+    })?;
+    // Never overwrite anything already in trash.
+    let f = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(archive_path)
+        .map_err(|e| match e.kind() {
+            ErrorKind::AlreadyExists => AlreadyExists {
+                path: archive_path.display().to_string(),
+            },
+            _ => WrmError(e.into()),
+        })?;
+    let mut builder = Builder::new(GzEncoder::new(f, Compression::default()));
+    builder.follow_symlinks(false);
+    let written = if path.is_dir() && !path.is_symlink() {
+        builder.append_dir_all(file_name, path)
+    } else {
+        builder.append_path_with_name(path, file_name)
+    }
+    .and_then(|_| builder.into_inner())
+    .and_then(|e| e.finish());
+    if let Err(e) = written {
+        // Don't leave a broken archive behind. It was created above, so nothing else is removed.
+        let _ = remove_file(archive_path);
+        return Err(WrmError(e.into()));
+    }
+    Ok(())
+}
+
+fn read_archive(archive_path: &Path, dir: &Path) -> Result<()> {
+    let f = File::open(archive_path)
+        .map_err(|e| e.into())
+        .map_err(WrmError)?;
+    let mut archive = Archive::new(GzDecoder::new(f));
+    archive.set_preserve_permissions(true);
+    archive.set_preserve_mtime(true);
+    archive.set_preserve_ownerships(is_root());
+    archive
+        .unpack(dir)
+        .map_err(|e| e.into())
+        .map_err(WrmError)?;
+    Ok(())
+}
+
+// Put back the ownership and the mode of a restored file.
+// Only root can give a file to another user. The mode of a symbolic link can't be changed.
+fn restore_owner(path: &Path, owner: &Owner) -> Result<()> {
+    if is_root() {
+        lchown(path, Some(owner.uid()), Some(owner.gid()))
+            .map_err(|e| e.into())
+            .map_err(WrmError)?;
+    }
+    if !path.is_symlink() {
+        set_permissions(path, Permissions::from_mode(owner.mode()))
+            .map_err(|e| e.into())
+            .map_err(WrmError)?;
+    }
+    Ok(())
+}
+
+// Resolves symbolic links in the parent, so that another path to the same file is recognized,
+// but not the file itself, which is what is removed.
+fn resolve(path: &Path) -> PathBuf {
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => parent
+            .canonicalize()
+            .map(|p| p.join(name))
+            .unwrap_or_else(|_| path.to_path_buf()),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn absolutize<P: AsRef<Path>>(path: P) -> Result<Filey> {
+    let mut temp = Filey::new(path);
+    Ok(temp
+        .absolutize()
+        .map_err(|e| e.into())
+        .map_err(WrmError)?
+        .clone())
+}
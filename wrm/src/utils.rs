@@ -0,0 +1,158 @@
+use crate::{Error::InvalidTime, Result};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone};
+use std::{
+    env,
+    ffi::CStr,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use walkdir::WalkDir;
+
+/// Returns the total size of a file or a directory in bytes.
+/// Symbolic links are not followed, and unreadable entries are ignored.
+pub fn size<P: AsRef<Path>>(path: P) -> u64 {
+    WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| !m.is_dir())
+        .map(|m| m.len())
+        .sum()
+}
+
+const KIB: u64 = 2_u64.pow(10);
+const MIB: u64 = 2_u64.pow(20);
+const GIB: u64 = 2_u64.pow(30);
+const TIB: u64 = 2_u64.pow(40);
+
+/// Formats a size in bytes like `1.5K`, `20M`.
+pub fn human_size(size: u64) -> String {
+    let (unit, suffix) = if size < KIB {
+        return size.to_string();
+    } else if size < MIB {
+        (KIB, "K")
+    } else if size < GIB {
+        (MIB, "M")
+    } else if size < TIB {
+        (GIB, "G")
+    } else {
+        (TIB, "T")
+    };
+    let value = size as f64 / unit as f64;
+    if value < 10.0 {
+        format!("{:.1}{}", value, suffix)
+    } else {
+        format!("{:.0}{}", value, suffix)
+    }
+}
+
+/// Returns the name of the user running wrm.
+pub fn current_user() -> String {
+    let uid = current_uid();
+    user_name(uid).unwrap_or_else(|| env::var("USER").unwrap_or_else(|_| uid.to_string()))
+}
+
+/// Returns the name of the user with the given uid.
+pub fn user_name(uid: u32) -> Option<String> {
+    // getpwuid_r rather than getpwuid, whose result is shared by every thread.
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+    let mut buffer: Vec<libc::c_char> = vec![0; 1024];
+    loop {
+        let e = unsafe {
+            libc::getpwuid_r(
+                uid,
+                &mut passwd,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            )
+        };
+        if e == libc::ERANGE && buffer.len() < 1 << 20 {
+            buffer.resize(buffer.len() * 2, 0);
+        } else if e != 0 || result.is_null() {
+            return None;
+        } else {
+            return Some(
+                unsafe { CStr::from_ptr(passwd.pw_name) }
+                    .to_string_lossy()
+                    .to_string(),
+            );
+        }
+    }
+}
+
+/// Returns the uid wrm is run as.
+pub fn current_uid() -> u32 {
+    unsafe { libc::geteuid() }
+}
+
+/// Returns true if wrm is run by root.
+pub fn is_root() -> bool {
+    current_uid() == 0
+}
+
+// getpwent has no reentrant version in POSIX, so wrm's own calls are serialized.
+static USERS: Mutex<()> = Mutex::new(());
+
+/// Returns the names, the uids and the home directories of all users in the user database.
+///
+/// It walks the database with getpwent, which other threads must not use at the same time.
+pub fn users() -> Vec<(String, u32, PathBuf)> {
+    let _lock = USERS.lock().unwrap_or_else(|e| e.into_inner());
+    let mut users = vec![];
+    unsafe {
+        libc::setpwent();
+        loop {
+            let passwd = libc::getpwent();
+            if passwd.is_null() {
+                break;
+            }
+            let name = CStr::from_ptr((*passwd).pw_name)
+                .to_string_lossy()
+                .to_string();
+            let home = CStr::from_ptr((*passwd).pw_dir)
+                .to_string_lossy()
+                .to_string();
+            users.push((name, (*passwd).pw_uid, PathBuf::from(home)));
+        }
+        libc::endpwent();
+    }
+    users
+}
+
+/// Parses a point in time.
+///
+/// Accepts RFC 3339(`2024-01-31T12:00:00+09:00`), `2024-01-31 12:00:00`, `2024-01-31 12:00`,
+/// `2024-01-31`, or a duration before now such as `30s`, `15m`, `2h`, `7d` and `2w`.
+pub fn parse_time(time: &str) -> Result<DateTime<Local>> {
+    let invalid = || InvalidTime {
+        time: time.to_string(),
+    };
+    let time = time.trim();
+    if let Ok(t) = DateTime::parse_from_rfc3339(time) {
+        return Ok(t.with_timezone(&Local));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(t) = NaiveDateTime::parse_from_str(time, format) {
+            return Local.from_local_datetime(&t).earliest().ok_or_else(invalid);
+        }
+    }
+    if let Ok(d) = NaiveDate::parse_from_str(time, "%Y-%m-%d") {
+        let t = d.and_hms_opt(0, 0, 0).ok_or_else(invalid)?;
+        return Local.from_local_datetime(&t).earliest().ok_or_else(invalid);
+    }
+    let (number, unit) =
+        time.split_at(time.len() - time.trim_start_matches(char::is_numeric).len());
+    let number: i64 = number.parse().map_err(|_| invalid())?;
+    let duration = match unit {
+        "s" => Duration::try_seconds(number),
+        "m" => Duration::try_minutes(number),
+        "h" => Duration::try_hours(number),
+        "d" => Duration::try_days(number),
+        "w" => Duration::try_weeks(number),
+        _ => None,
+    }
+    .ok_or_else(invalid)?;
+    Ok(Local::now() - duration)
+}
@@ -0,0 +1,87 @@
+use crate::{Error::WrmError, Result};
+use filey::Filey;
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+/// The environment variable to select the trash root instead of `~/.config/wrm`.
+pub const TRASH_DIR_ENV: &str = "WRM_TRASH_DIR";
+
+const DEFAULT_DIR: &str = "~/.config/wrm";
+
+/// Paths in the trash root.
+///
+/// The root directory contains `trash/` where files are moved to, `list.json` which records
+/// where they came from, `log.jsonl`, the audit log, and `hooks.json`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WrmPath {
+    dir: PathBuf,
+}
+
+impl Default for WrmPath {
+    /// `~/.config/wrm`, which isn't expanded yet.
+    fn default() -> Self {
+        WrmPath::new(DEFAULT_DIR)
+    }
+}
+
+impl WrmPath {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        WrmPath {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Returns the trash root given by `$WRM_TRASH_DIR`, or the default one.
+    pub fn from_env() -> Self {
+        match env::var_os(TRASH_DIR_ENV) {
+            Some(dir) if !dir.is_empty() => WrmPath::new(dir),
+            _ => WrmPath::default(),
+        }
+    }
+
+    /// Expands `~` and makes the root absolute.
+    pub fn expanded(&self) -> Result<Self> {
+        let mut dir = Filey::new(&self.dir);
+        let dir = dir
+            .expand_user()
+            .map_err(|e| e.into())
+            .map_err(WrmError)?
+            .absolutize()
+            .map_err(|e| e.into())
+            .map_err(WrmError)?;
+        Ok(WrmPath::new(dir.path()))
+    }
+
+    /// Returns the trash root.
+    pub fn dir(&self) -> &PathBuf {
+        &self.dir
+    }
+
+    /// Returns the directory where files are moved to.
+    pub fn trash(&self) -> PathBuf {
+        self.dir.join("trash")
+    }
+
+    /// Returns the path to list.json.
+    pub fn list(&self) -> PathBuf {
+        self.dir.join("list.json")
+    }
+
+    /// Returns the path to the audit log.
+    pub fn log(&self) -> PathBuf {
+        self.dir.join("log.jsonl")
+    }
+
+    /// Returns the path to hooks.json.
+    pub fn hooks(&self) -> PathBuf {
+        self.dir.join("hooks.json")
+    }
+}
+
+impl AsRef<Path> for WrmPath {
+    fn as_ref(&self) -> &Path {
+        &self.dir
+    }
+}